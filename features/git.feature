@@ -17,6 +17,15 @@ Feature: Git operations in the password store
     And a password is edited
     Then the repository is clean and contains a new commit
 
+  Scenario: Show the history of an edited password
+    Given a password store exists
+    And the password store uses git
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And a password is edited
+    Then the history of the password shows the edit and the previous content
+
   Scenario: Commit a removed password
     Given a password store exists
     And the password store uses git
@@ -26,6 +35,92 @@ Feature: Git operations in the password store
     And a password is removed
     Then the repository is clean and contains a new commit
 
+  Scenario: Restore a removed password
+    Given a password store exists
+    And the password store uses git
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And a password is removed
+    And "Manufacturers/Sokor" is restored from before "Remove 'Manufacturers/Sokor'"
+    Then the restored entry is a password
+    And the restored password "Manufacturers/Sokor" has the passphrase "pum-yIghoSQo'" and 2 lines
+    And the restore of "Manufacturers/Sokor" is committed
+
+  Scenario: Roll back an edited password
+    Given a password store exists
+    And the password store uses git
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And a password is edited
+    And "Manufacturers/Sokor" is restored from before "Edit password for 'Manufacturers/Sokor'"
+    Then the restored password "Manufacturers/Sokor" has the passphrase "pum-yIghoSQo'" and 2 lines
+    And the restore of "Manufacturers/Sokor" is committed
+
+  Scenario: A failed restore of a directory leaves the passwords untouched
+    Given a password store exists
+    And the password store uses git
+    And passwords are stored in the password store
+    And the password "Manufacturers/Yoyodyne" cannot be replaced
+    And a password store is opened
+    When the password store is successfully opened
+    And a password is edited
+    And restoring "Manufacturers" from before "Edit password for 'Manufacturers/Sokor'" fails
+    And the password "Manufacturers/Yoyodyne" can be replaced again
+    Then the password "Manufacturers/Sokor" still contains the line "Note: Picard already knows..."
+    And no temporary files are left in the store
+
+  Scenario: Restore a removed password for the current gpg-ids
+    Given a password store exists
+    And the password store uses git
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And a password is removed
+    And the gpg-ids of the store are extended
+    And "Manufacturers/Sokor" is restored from before "Remove 'Manufacturers/Sokor'"
+    Then the restored password "Manufacturers/Sokor" is encrypted for 2 recipients
+    And the restore of "Manufacturers/Sokor" is committed
+
+  Scenario: Changes made in a batch are committed at once
+    Given a password store exists
+    And the password store uses git
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And passwords are changed in a batch
+    Then the batch is committed at once
+
+  Scenario: Changes of a failed batch are rolled back
+    Given a password store exists
+    And the password store uses git
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And passwords are changed in a batch which fails
+    Then the batch is rolled back
+
+  Scenario: Reencryption for changed gpg-ids is committed at once
+    Given a password store exists
+    And the password store uses git
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And the gpg-id of the store is edited
+    Then the gpg-ids of the store are changed in a single commit
+
+  Scenario: Reencryption for changed gpg-ids keeps uncommitted changes
+    Given a password store exists
+    And the password store uses git
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And an untracked file is added to the store
+    And the gpg-id of the store is edited
+    Then the untracked file is still in the store
+    And a store error is emitted that the batch was committed change by change
+
   Scenario: Commit a renamed password
     Given a password store exists
     And the password store uses git
@@ -35,6 +130,24 @@ Feature: Git operations in the password store
     And a password is renamed
     Then the repository is clean and contains a new commit
 
+  Scenario: Show the history of a renamed password
+    Given a password store exists
+    And the password store uses git
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And a password is renamed
+    Then the history of the password follows the rename
+
+  Scenario: Commit a moved password
+    Given a password store exists
+    And the password store uses git
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And a password is moved
+    Then the repository is clean and contains a new commit
+
   Scenario: Commit a password in a new directory
     Given a password store exists
     And the password store uses git
@@ -62,6 +175,18 @@ Feature: Git operations in the password store
     And a directory is removed
     Then the repository is clean and contains a new commit
 
+  Scenario: Restore a removed directory
+    Given a password store exists
+    And the password store uses git
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And a directory is removed
+    And "Entertainment" is restored from before "Remove 'Entertainment'"
+    Then the restored entry is a directory
+    And the restored password "Entertainment/Holo Deck/Broht & Forrester" has the passphrase "fun-times1337" and 2 lines
+    And the restore of "Entertainment" is committed
+
   Scenario: Query the status of an unaltered password store
     Given a password store exists
     And the password store uses git
@@ -80,6 +205,7 @@ Feature: Git operations in the password store
     And a password is created
     And the commit is pushed to the remote
     Then pushing the commit succeeds
+    And the pushed branch is reported as accepted by the remote
 
   Scenario: Push of non-fastforward commits to the git remote fails
     Given a password store exists
@@ -93,6 +219,37 @@ Feature: Git operations in the password store
     And the commit is pushed to the remote
     Then pushing the commit fails
 
+  Scenario: Credentials for the git remote are requested from the application
+    Given a password store exists
+    And the password store uses git
+    And the repository has a remote which requires authentication
+    And credentials are requested from the application
+    And a password store is opened
+    When the password store is successfully opened
+    Then fetching the remote fails without credentials from the application
+
+  Scenario: Commits are signed if git is configured to sign them
+    Given a password store exists
+    And the password store uses git
+    And passwords are stored in the password store
+    And commits are signed with "test@key.email"
+    And a passphrase provider is available
+    And a password store is opened
+    When the password store is successfully opened
+    And a password is edited
+    Then the last commit is signed by "test@key.email"
+
+  Scenario: Pulling unsigned changes fails if unsigned commits are rejected
+    Given a password store exists
+    And the password store uses git
+    And passwords are stored in the password store
+    And the repository has a remote
+    And the repository's remote contains new commits
+    And a password store is opened
+    When the password store is successfully opened
+    And unsigned commits are rejected
+    Then pulling the unsigned changes from the remote fails
+
   Scenario: Pull fast-forward changes from git remote without interaction
     Given a password store exists
     And the password store uses git
@@ -120,6 +277,20 @@ Feature: Git operations in the password store
     And the remote's commits are merged
     And the repository is clean
 
+  Scenario: Show the history of a password changed in merged remote commits
+    Given a password store exists
+    And the password store uses git
+    And passwords are stored in the password store
+    And the repository has a remote
+    And the repository's remote contains new commits
+    And a password store is opened
+    When the password store is successfully opened
+    And a new password is created
+    And changes are pulled from the remote
+    Then no conflicts need to be resolved
+    And the remote's commits are merged
+    And the history of the password contains the merged remote commit
+
   Scenario: Pull non-fast-forward changes from the git remote with manual merging and resolve merge conflict by letting the user resolve it using decrypted passwords
     Given a password store exists
     And the password store uses git
@@ -259,6 +430,16 @@ Feature: Git operations in the password store
     And the repository's remote is fetched
     Then the git status contains new commits on the remote
 
+  Scenario: Progress of fetching the remote is reported
+    Given a password store exists
+    And the password store uses git
+    And passwords are stored in the password store
+    And the repository has a remote
+    And the repository's remote contains new commits
+    And a password store is opened
+    When the password store is successfully opened
+    Then fetching the remote reports the received objects
+
   Scenario: Uncommitted files changed outside of this library are reported in status
     Given a password store exists
     And the password store uses git
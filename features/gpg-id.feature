@@ -58,3 +58,58 @@ Feature: Using GPG IDs
     And a password store is opened
     When the password store is successfully opened
     Then the GPG IDs in the password store are signed
+
+  Scenario: Passwords are reencrypted when a GPG ID is added
+    Given a password store exists
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And a GPG ID is added to the store
+    Then the password "Manufacturers/Sokor" is encrypted for 2 recipients
+    And no temporary files are left in the store
+
+  Scenario: Reencryption for new GPG IDs is all-or-nothing
+    Given a password store exists
+    And passwords are stored in the password store
+    And the password "Phone" is corrupted
+    And a password store is opened
+    When the password store is successfully opened
+    And a GPG ID is added to the store, which fails
+    Then the GPG IDs of the store are unchanged
+    And the password "Manufacturers/Sokor" is encrypted for 1 recipient
+    And no temporary files are left in the store
+
+  Scenario: Passwords are restored if swapping in a reencrypted password fails
+    Given a password store exists
+    And passwords are stored in the password store
+    And the password "Phone" cannot be replaced
+    And the passwords in the store are sorted
+    And a password store is opened
+    When the password store is successfully opened
+    And a GPG ID is added to the store, which fails
+    And the password "Phone" can be replaced again
+    Then the GPG IDs of the store are unchanged
+    And the password "Manufacturers/Sokor" is encrypted for 1 recipient
+    And the password "Phone" is encrypted for 1 recipient
+    And no temporary files are left in the store
+
+  Scenario: Moving a password reencrypts it for the GPG IDs of its new directory
+    Given a password store exists
+    And passwords are stored in the password store
+    And the directory "Manufacturers" is also encrypted for "test2@key.email"
+    And a password store is opened
+    When the password store is successfully opened
+    And a password is moved
+    Then the password "Sokor" is encrypted for 1 recipient
+    And no temporary files are left in the store
+
+  Scenario: Copying a password encrypts the copy for the GPG IDs of its new directory
+    Given a password store exists
+    And passwords are stored in the password store
+    And the directory "Manufacturers" is also encrypted for "test2@key.email"
+    And a password store is opened
+    When the password store is successfully opened
+    And a password is copied
+    Then the password "Sokor" is encrypted for 1 recipient
+    And the password "Manufacturers/Sokor" is encrypted for 2 recipients
+    And no temporary files are left in the store
@@ -27,7 +27,60 @@ Feature: Initialize a new password store
     And the password store's directory exists
     And the password store's directory contains a GPG ID file
 
+  Scenario: A new password store is created with signed GPG IDs
+    Given no password store exists
+    And a signing key is manually specified
+    And a new password store is initialized
+    When a new password store is successfully created
+    Then the password store has no errors
+    And the password store's GPG ID file is signed
+
+  Scenario: An edited GPG ID file is rejected
+    Given no password store exists
+    And a signing key is manually specified
+    And a new password store is initialized
+    When a new password store is successfully created
+    And the store's GPG ID file is edited
+    Then the GPG IDs of the store cannot be verified
+
+  Scenario: A GPG ID file without signature is rejected
+    Given no password store exists
+    And a signing key is manually specified
+    And a new password store is initialized
+    When a new password store is successfully created
+    And the store's GPG ID signature is removed
+    Then the GPG IDs of the store cannot be verified
+
   Scenario: A new password store fails to create if it already exists
     Given a password store exists
     And a new password store is initialized
     Then the initialization of the password store fails
+
+  Scenario: A new password store is created with a git repository
+    Given no password store exists
+    And the git user is configured
+    And a new password store is initialized
+    When a new password store is successfully created
+    And git is initialized for the password store
+    Then the password store has a git repository
+    And the git repository contains the initial commit
+    And the repository is clean
+
+  Scenario: A new password store is created with a git repository and a remote
+    Given no password store exists
+    And the git user is configured
+    And a new password store is initialized
+    When a new password store is successfully created
+    And git is initialized for the password store with the remote "origin" at "https://git.example.com/store.git"
+    Then the password store has the git remote "origin" at "https://git.example.com/store.git"
+
+  Scenario: A password store is cloned from a remote
+    Given a password store exists
+    And the password store uses git
+    And passwords are stored in the password store
+    And the repository has a remote
+    And the password store is cloned from the remote
+    When the password store is successfully cloned
+    Then the password store has no errors
+    And the password store contains passwords
+    And the password store has a git repository
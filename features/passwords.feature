@@ -36,7 +36,7 @@ Feature: Working with passwords
     And passwords are stored in the password store
     And a password store is opened
     When the password store is successfully opened
-    And a password is opened
+    And the password "Manufacturers/Sokor" is opened
     Then a QR code can be created for a comment
 
   Scenario: Opening a password and creating a QR code for entry
@@ -44,7 +44,7 @@ Feature: Working with passwords
     And passwords are stored in the password store
     And a password store is opened
     When the password store is successfully opened
-    And a password is opened
+    And the password "Entertainment/Holo Deck/Broht & Forrester" is opened
     Then a QR code can be created for an entry
 
   Scenario: Opening a password and creating a QR code for whole password file
@@ -69,6 +69,13 @@ Feature: Working with passwords
     And a new password is created
     Then the new password appears in the password store
 
+  Scenario: Creating a parsed password
+    Given a password store exists
+    And a password store is opened
+    When the password store is successfully opened
+    And a password with a comment and an entry is created
+    Then the new password is stored as "Bridge.gpg" with its comment and entry
+
   Scenario: Generating a passphrase and creating it
     Given a password store exists
     And a password store is opened
@@ -180,3 +187,45 @@ Feature: Working with passwords
     When the password store is successfully opened
     And a password is copied
     Then a new password exists
+
+  Scenario: Generating a TOTP code compatible with pass-otp
+    Given a password store exists
+    And OTP secrets are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    Then the TOTP code 59 seconds after the epoch is 94287082 and rotates in 1 second
+
+  Scenario: Generating a HOTP code compatible with pass-otp
+    Given a password store exists
+    And OTP secrets are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    Then the next HOTP code is 359152 and the counter is saved as 2
+
+  Scenario: Inserting an OTP-only password from an otpauth URI
+    Given a password store exists
+    And a password store is opened
+    When the password store is successfully opened
+    And an OTP-only password is inserted from an otpauth URI
+    Then the new OTP password generates the TOTP code 94287082 59 seconds after the epoch
+
+  Scenario: Inserting an OTP secret with an invalid base32 secret
+    Given a password store exists
+    And a password store is opened
+    When the password store is successfully opened
+    Then inserting an OTP secret with an invalid base32 secret fails
+
+  Scenario: Adding an OTP secret to an existing password
+    Given a password store exists
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And an OTP secret is added to an existing password
+    Then the password keeps its passphrase and generates the TOTP code 94287082 59 seconds after the epoch
+
+  Scenario: Reading an OTP secret from a QR image
+    Given a password store exists
+    And a password store is opened
+    When the password store is successfully opened
+    And an OTP-only password is inserted from an otpauth URI
+    Then the OTP secret of the new password can be read back from a QR image
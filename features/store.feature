@@ -32,6 +32,69 @@ Feature: General password store operations
     And a non-existent password is searched in the password store
     Then the password is not found
 
+  Scenario: Searching for passwords without options matches the absolute path
+    Given a password store exists
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And the password store is searched for "manufacturers/strutco.gpg" without options
+    Then exactly the entries "Manufacturers/StrutCo.gpg" are found
+
+  Scenario: Searching for passwords with options matches the path without extension
+    Given a password store exists
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And the password store is searched with the substring pattern "manufacturers/strutco.gpg" on the path
+    Then exactly the entries "" are found
+
+  Scenario: Searching for passwords with a glob pattern
+    Given a password store exists
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And the password store is searched with the glob pattern "manufacturers/s*" on the path
+    Then exactly the entries "Manufacturers/Sokor.gpg, Manufacturers/StrutCo.gpg" are found
+
+  Scenario: Searching for passwords with a recursive glob pattern
+    Given a password store exists
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And the password store is searched with the glob pattern "Entertainment/**/Broht*" on the path
+    Then exactly the entries "Entertainment/Holo Deck/Broht & Forrester.gpg" are found
+
+  Scenario: Searching for passwords with a regular expression
+    Given a password store exists
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And the password store is searched with the regex pattern "^(phone|sokor)$" on the name
+    Then exactly the entries "Manufacturers/Sokor.gpg, Phone.gpg" are found
+
+  Scenario: Searching for passwords by exact name with case-sensitivity
+    Given a password store exists
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And the password store is case-sensitively searched with the exact pattern "phone" on the name
+    Then exactly the entries "" are found
+
+  Scenario: Searching for passwords with an invalid regular expression
+    Given a password store exists
+    And a password store is opened
+    When the password store is successfully opened
+    Then searching the password store with the regex pattern "(" fails
+
+  Scenario: Fuzzy searching for passwords ranks the best match first
+    Given a password store exists
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And the password store is fuzzy searched for "mstr"
+    Then the best fuzzy match is "Manufacturers/StrutCo" with all query characters highlighted
+    And no fuzzy match is found for "Phone"
+
   Scenario: Searching for password content in the password store succeeds
     Given a password store exists
     And passwords are stored in the password store
@@ -47,6 +110,112 @@ Feature: General password store operations
     And content of a non-existing password is searched in the password store
     Then the password is not found
 
+  Scenario: Grepping password content with a regular expression
+    Given a password store exists
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And the password store is grepped for the regex "^pin: \d+$"
+    Then only "Phone.gpg" matches at line 0 from byte 0 to 9
+
+  Scenario: Grepping password content case-sensitively on multiple threads
+    Given a password store exists
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And the password store is grepped case-sensitively for "O--" on 4 threads
+    Then only "Phone.gpg" matches on lines 3, 5 and 7
+
+  Scenario: Grepping password content reports passwords that cannot be decrypted
+    Given a password store exists
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And a password file is corrupted
+    And the password store is grepped case-sensitively for "O--" on 4 threads
+    Then only "Phone.gpg" matches on lines 3, 5 and 7
+    And the grep reports a decryption error for "Manufacturers/StrutCo.gpg"
+
+  Scenario: Grepping password content through the encrypted search index
+    Given a password store exists
+    And passwords are stored in the password store
+    And the password store keeps a search index
+    And a password store is opened
+    When the password store is successfully opened
+    And the password store is grepped case-sensitively for "O--" on 4 threads skipping passphrases
+    Then only "Phone.gpg" matches on lines 3, 5 and 7
+    And the search index does not contain plain text
+
+  Scenario: Grepping password content skipping passphrases
+    Given a password store exists
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And the password store is grepped for the regex "^pin: \d+$" skipping passphrases
+    Then no password content matches
+
+  Scenario: The search index never contains passphrases
+    Given a password store exists
+    And passwords are stored in the password store
+    And the password store keeps a search index
+    And a password store is opened
+    When the password store is successfully opened
+    And the password store is grepped for the regex "^pin: \d+$" skipping passphrases
+    Then no password content matches
+
+  Scenario: The search index does not change grep results
+    Given a password store exists
+    And passwords are stored in the password store
+    And the password store keeps a search index
+    And a password store is opened
+    When the password store is successfully opened
+    And the password store is grepped for the regex "^pin: \d+$"
+    Then only "Phone.gpg" matches at line 0 from byte 0 to 9
+
+  Scenario: The search index follows changes made through this library
+    Given a password store exists
+    And passwords are stored in the password store
+    And the password store keeps a search index
+    And a password store is opened
+    When the password store is successfully opened
+    And the password store is grepped case-sensitively for "O--" on 4 threads skipping passphrases
+    And the line "O--O--O" is appended to "Manufacturers/Sokor" with this library
+    And the password store is grepped case-sensitively for "O--" on 4 threads skipping passphrases
+    Then "Manufacturers/Sokor.gpg" matches at line 2 as well
+
+  Scenario: Searching parsed password entries by exact value
+    Given a password store exists
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And the password entries are searched for "user" with the exact value "LaForge"
+    Then only "Manufacturers/Yoyodyne.gpg" has the entry "user" with value "laforge" at line 0
+
+  Scenario: Searching parsed password entries with a regular expression
+    Given a password store exists
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And the password entries are searched for "username" with the regex value "^geo"
+    Then only "Entertainment/Holo Deck/Broht & Forrester.gpg" has the entry "username" with value "geordi" at line 0
+
+  Scenario: Searching parsed password entries case-sensitively
+    Given a password store exists
+    And passwords are stored in the password store
+    And a password store is opened
+    When the password store is successfully opened
+    And the password entries are case-sensitively searched for "User" with the exact value "laforge"
+    Then no password entries are found
+
+  Scenario: Searching parsed password entries through the encrypted search index
+    Given a password store exists
+    And passwords are stored in the password store
+    And the password store keeps a search index
+    And a password store is opened
+    When the password store is successfully opened
+    And the password entries are searched for "user" with the exact value "LaForge"
+    Then only "Manufacturers/Yoyodyne.gpg" has the entry "user" with value "laforge" at line 0
+
   Scenario: New files in the password store should respect the automatically detected umask
     Given a password store exists
     And the password store umask is automatically detected
@@ -0,0 +1,171 @@
+use std::{
+    fmt::{Debug, Formatter},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use git2::{Config, Cred, CredentialType, RemoteCallbacks};
+
+#[derive(Debug, Clone)]
+pub struct CredentialsRequest {
+    url: String,
+    username: Option<String>,
+    allowed: CredentialType,
+    attempt: usize,
+}
+
+impl CredentialsRequest {
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    // the user name from the remote's URL, e.g. "git" for git@example.com:store.git
+    pub fn username(&self) -> Option<&str> {
+        self.username.as_deref()
+    }
+
+    pub fn allowed(&self) -> CredentialType {
+        self.allowed
+    }
+
+    // starts at 1, a higher number means the previous credentials were rejected
+    pub fn attempt(&self) -> usize {
+        self.attempt
+    }
+}
+
+#[derive(Clone)]
+pub enum CredentialsProvider {
+    /// ssh-agent for SSH remotes and the git credential helpers for HTTPS remotes
+    Automatic,
+    /// Keys loaded into the running ssh-agent
+    SshAgent,
+    /// A private key file which may be protected by a passphrase
+    SshKey {
+        private_key: PathBuf,
+        public_key: Option<PathBuf>,
+        passphrase: Option<String>,
+    },
+    /// The `credential.helper` configured for git
+    CredentialHelper,
+    /// Ask the application, e.g. to show a login dialog
+    Manual(Arc<Mutex<dyn FnMut(&CredentialsRequest) -> Result<Cred, git2::Error> + Send>>),
+}
+
+fn rejected() -> git2::Error {
+    git2::Error::from_str("Authentication failed, no further credentials available")
+}
+
+impl CredentialsProvider {
+    fn credentials(
+        &self,
+        request: &CredentialsRequest,
+        config: &Config,
+        tried: &mut CredentialType,
+    ) -> Result<Cred, git2::Error> {
+        if let CredentialsProvider::Manual(provider) = self {
+            let mut provide = provider.lock().expect("Credentials provider poisoned");
+            return (&mut *provide)(request);
+        }
+
+        let username = request.username().unwrap_or("git");
+        if request.allowed.contains(CredentialType::USERNAME) {
+            return Cred::username(username);
+        }
+
+        // every built-in strategy gets exactly one chance, libgit2 would ask forever otherwise
+        let strategy = if request.allowed.contains(CredentialType::SSH_KEY) {
+            CredentialType::SSH_KEY
+        } else if request.allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            CredentialType::USER_PASS_PLAINTEXT
+        } else if request.allowed.contains(CredentialType::DEFAULT) {
+            CredentialType::DEFAULT
+        } else {
+            return Err(rejected());
+        };
+        if tried.contains(strategy) {
+            return Err(rejected());
+        }
+        tried.insert(strategy);
+
+        match (self, strategy) {
+            (
+                CredentialsProvider::SshKey {
+                    private_key,
+                    public_key,
+                    passphrase,
+                },
+                CredentialType::SSH_KEY,
+            ) => Cred::ssh_key(
+                username,
+                public_key.as_deref(),
+                private_key,
+                passphrase.as_deref(),
+            ),
+            (
+                CredentialsProvider::Automatic | CredentialsProvider::SshAgent,
+                CredentialType::SSH_KEY,
+            ) => Cred::ssh_key_from_agent(username),
+            (
+                CredentialsProvider::Automatic | CredentialsProvider::CredentialHelper,
+                CredentialType::USER_PASS_PLAINTEXT,
+            ) => Cred::credential_helper(config, request.url(), request.username()),
+            (_, CredentialType::DEFAULT) => Cred::default(),
+            _ => Err(rejected()),
+        }
+    }
+
+    pub(crate) fn callbacks<'a>(&self, config: Config) -> RemoteCallbacks<'a> {
+        let provider = self.clone();
+        let mut attempt = 0;
+        let mut tried = CredentialType::empty();
+
+        let mut callbacks = RemoteCallbacks::new();
+        callbacks.credentials(move |url, username, allowed| {
+            attempt += 1;
+            let request = CredentialsRequest {
+                url: url.to_owned(),
+                username: username.map(str::to_owned),
+                allowed,
+                attempt,
+            };
+            provider.credentials(&request, &config, &mut tried)
+        });
+        callbacks
+    }
+}
+
+impl Debug for CredentialsProvider {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CredentialsProvider::Automatic => f.debug_tuple("Automatic").finish(),
+            CredentialsProvider::SshAgent => f.debug_tuple("SshAgent").finish(),
+            CredentialsProvider::SshKey {
+                private_key,
+                public_key,
+                passphrase,
+            } => f
+                .debug_struct("SshKey")
+                .field("private_key", private_key)
+                .field("public_key", public_key)
+                .field("passphrase", &passphrase.as_ref().map(|_| "********"))
+                .finish(),
+            CredentialsProvider::CredentialHelper => f.debug_tuple("CredentialHelper").finish(),
+            CredentialsProvider::Manual(_) => f
+                .debug_tuple("Manual")
+                .field(&String::from(
+                    "(&CredentialsRequest) -> Result<git2::Cred, git2::Error>",
+                ))
+                .finish(),
+        }
+    }
+}
+
+impl<F> From<F> for CredentialsProvider
+where
+    F: FnMut(&CredentialsRequest) -> Result<Cred, git2::Error> + Send + 'static,
+{
+    fn from(func: F) -> CredentialsProvider {
+        CredentialsProvider::Manual(Arc::new(Mutex::new(func)))
+    }
+}
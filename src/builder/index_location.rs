@@ -0,0 +1,51 @@
+use std::path::{Path, PathBuf};
+
+use directories::BaseDirs;
+
+use crate::StoreError;
+
+#[derive(Debug, Clone)]
+pub enum IndexLocation {
+    /// Do not keep a search index
+    Disabled,
+    /// One index per store in the user's cache directory
+    Automatic,
+    /// Override the path of the encrypted index file
+    Manual(PathBuf),
+}
+
+impl<P> From<P> for IndexLocation
+where
+    P: Into<PathBuf>,
+{
+    fn from(path: P) -> IndexLocation {
+        IndexLocation::Manual(path.into())
+    }
+}
+
+impl IndexLocation {
+    pub(crate) fn resolve(&self, store: &Path) -> Result<Option<PathBuf>, StoreError> {
+        match self {
+            IndexLocation::Disabled => Ok(None),
+            IndexLocation::Manual(path) => Ok(Some(path.clone())),
+            IndexLocation::Automatic => {
+                // the store path is escaped so different stores never share an index file
+                let name = store
+                    .display()
+                    .to_string()
+                    .replace('%', "%25")
+                    .replace('/', "%2F");
+                BaseDirs::new()
+                    .map(|base_dirs| {
+                        Some(
+                            base_dirs
+                                .cache_dir()
+                                .join("libpass")
+                                .join(format!("{}.gpg", name)),
+                        )
+                    })
+                    .ok_or(StoreError::NoCacheDir)
+            }
+        }
+    }
+}
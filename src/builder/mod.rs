@@ -1,3 +1,5 @@
+mod credentials_provider;
+mod index_location;
 mod location;
 mod passphrase_provider;
 mod signing_key;
@@ -5,6 +7,8 @@ mod sorting;
 mod store_builder;
 mod umask;
 
+pub use credentials_provider::*;
+pub use index_location::*;
 pub use location::*;
 pub use passphrase_provider::*;
 pub use signing_key::*;
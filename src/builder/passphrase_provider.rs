@@ -1,15 +1,49 @@
 use std::{
     fmt::{Debug, Formatter},
     io::Write,
-    sync::Arc,
+    sync::{Arc, Mutex},
 };
 
-use gpgme::PassphraseRequest;
+use gpgme::{Context, PassphraseRequest, PinentryMode, Protocol};
 
 #[derive(Clone)]
 pub enum PassphraseProvider {
     SystemAgent,
-    Manual(Arc<dyn FnMut(PassphraseRequest, &mut dyn Write) -> Result<(), gpgme::Error>>),
+    Manual(
+        Arc<Mutex<dyn FnMut(PassphraseRequest, &mut dyn Write) -> Result<(), gpgme::Error> + Send>>,
+    ),
+}
+
+impl PassphraseProvider {
+    pub(crate) fn context(&self) -> gpgme::Result<Context> {
+        let mut ctx = Context::from_protocol(Protocol::OpenPgp)?;
+        if let PassphraseProvider::Manual(_) = self {
+            // loopback mode lets gpg ask us instead of the system pinentry
+            ctx.set_pinentry_mode(PinentryMode::Loopback)?;
+        }
+
+        Ok(ctx)
+    }
+
+    pub(crate) fn provide<T>(
+        &self,
+        ctx: &mut Context,
+        operation: impl FnOnce(&mut Context) -> T,
+    ) -> T {
+        match self {
+            PassphraseProvider::SystemAgent => operation(ctx),
+            PassphraseProvider::Manual(provider) => {
+                let provider = provider.clone();
+                ctx.with_passphrase_provider(
+                    move |request: PassphraseRequest, out: &mut dyn Write| {
+                        let mut provide = provider.lock().expect("Passphrase provider poisoned");
+                        (&mut *provide)(request, out)
+                    },
+                    operation,
+                )
+            }
+        }
+    }
 }
 
 impl Debug for PassphraseProvider {
@@ -32,9 +66,9 @@ impl Debug for PassphraseProvider {
 
 impl<F> From<F> for PassphraseProvider
 where
-    F: FnMut(PassphraseRequest, &mut dyn Write) -> Result<(), gpgme::Error> + 'static,
+    F: FnMut(PassphraseRequest, &mut dyn Write) -> Result<(), gpgme::Error> + Send + 'static,
 {
     fn from(func: F) -> PassphraseProvider {
-        PassphraseProvider::Manual(Arc::new(func))
+        PassphraseProvider::Manual(Arc::new(Mutex::new(func)))
     }
 }
@@ -1,3 +1,5 @@
+use std::env;
+
 #[derive(Debug, Clone)]
 pub enum SigningKey {
     Automatic,
@@ -12,3 +14,13 @@ where
         SigningKey::Manual(signing_key.into())
     }
 }
+
+impl SigningKey {
+    pub(crate) fn resolve(&self) -> Vec<String> {
+        let keys = match self {
+            SigningKey::Automatic => env::var("PASSWORD_STORE_SIGNING_KEY").unwrap_or_default(),
+            SigningKey::Manual(keys) => keys.clone(),
+        };
+        keys.split_whitespace().map(str::to_owned).collect()
+    }
+}
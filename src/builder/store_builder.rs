@@ -1,12 +1,17 @@
-use crate::{Location, PassphraseProvider, SigningKey, Sorting, Store, StoreError, Umask};
+use crate::{
+    CredentialsProvider, IndexLocation, Location, PassphraseProvider, SigningKey, Sorting, Store,
+    StoreError, Umask,
+};
 
 #[derive(Debug, Clone)]
 pub struct StoreBuilder {
-    location: Location,
-    passphrase_provider: PassphraseProvider,
-    umask: Umask,
-    signing_key: SigningKey,
-    sorting: Sorting,
+    pub(crate) location: Location,
+    pub(crate) passphrase_provider: PassphraseProvider,
+    pub(crate) credentials_provider: CredentialsProvider,
+    pub(crate) umask: Umask,
+    pub(crate) signing_key: SigningKey,
+    pub(crate) sorting: Sorting,
+    pub(crate) search_index: IndexLocation,
 }
 
 impl Default for StoreBuilder {
@@ -14,9 +19,11 @@ impl Default for StoreBuilder {
         Self {
             location: Location::Automatic,
             passphrase_provider: PassphraseProvider::SystemAgent,
+            credentials_provider: CredentialsProvider::Automatic,
             umask: Umask::Automatic,
             signing_key: SigningKey::Automatic,
             sorting: Sorting::NONE,
+            search_index: IndexLocation::Disabled,
         }
     }
 }
@@ -38,6 +45,14 @@ impl StoreBuilder {
         self
     }
 
+    pub fn credentials_provider<C>(&mut self, credentials_provider: C) -> &mut Self
+    where
+        C: Into<CredentialsProvider>,
+    {
+        self.credentials_provider = credentials_provider.into();
+        self
+    }
+
     pub fn umask<U>(&mut self, umask: U) -> &mut Self
     where
         U: Into<Umask>,
@@ -62,24 +77,23 @@ impl StoreBuilder {
         self
     }
 
+    pub fn search_index<I>(&mut self, search_index: I) -> &mut Self
+    where
+        I: Into<IndexLocation>,
+    {
+        self.search_index = search_index.into();
+        self
+    }
+
     pub fn init(&self, gpg_id: &str) -> Result<Store, StoreError> {
-        Store::init(
-            self.location.clone(),
-            self.passphrase_provider.clone(),
-            self.umask.clone(),
-            self.signing_key.clone(),
-            self.sorting,
-            gpg_id,
-        )
+        Store::init(self, gpg_id)
+    }
+
+    pub fn clone_remote(&self, url: &str) -> Result<Store, StoreError> {
+        Store::clone(self, url)
     }
 
     pub fn open(&self) -> Result<Store, StoreError> {
-        Store::open(
-            self.location.clone(),
-            self.passphrase_provider.clone(),
-            self.umask.clone(),
-            self.signing_key.clone(),
-            self.sorting,
-        )
+        Store::open(self)
     }
 }
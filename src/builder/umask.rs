@@ -1,3 +1,7 @@
+use std::env;
+
+use crate::StoreError;
+
 #[derive(Debug, Clone)]
 pub enum Umask {
     Automatic,
@@ -12,3 +16,17 @@ where
         Umask::Manual(mask.into())
     }
 }
+
+impl Umask {
+    pub(crate) fn resolve(&self) -> Result<u32, StoreError> {
+        match self {
+            Umask::Manual(mask) => Ok(*mask),
+            Umask::Automatic => match env::var("PASSWORD_STORE_UMASK") {
+                Ok(mask) => {
+                    u32::from_str_radix(&mask, 8).map_err(|_| StoreError::InvalidUmask(mask))
+                }
+                Err(_) => Ok(0o077),
+            },
+        }
+    }
+}
@@ -0,0 +1,336 @@
+use std::{
+    collections::HashMap,
+    env,
+    fmt::{Debug, Formatter},
+    io::{self, Write},
+    process::{Command, Stdio},
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::StoreError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Selection {
+    Clipboard,
+    Primary,
+    Secondary,
+}
+
+impl Selection {
+    fn name(&self) -> &'static str {
+        match self {
+            Selection::Clipboard => "clipboard",
+            Selection::Primary => "primary",
+            Selection::Secondary => "secondary",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum XSelection {
+    Automatic,
+    Manual(Selection),
+}
+
+impl From<Selection> for XSelection {
+    fn from(selection: Selection) -> XSelection {
+        XSelection::Manual(selection)
+    }
+}
+
+impl XSelection {
+    fn resolve(&self) -> Result<Selection, StoreError> {
+        match self {
+            XSelection::Manual(selection) => Ok(*selection),
+            XSelection::Automatic => match env::var("PASSWORD_STORE_X_SELECTION") {
+                Ok(selection) => match selection.as_str() {
+                    "clipboard" => Ok(Selection::Clipboard),
+                    "primary" => Ok(Selection::Primary),
+                    "secondary" => Ok(Selection::Secondary),
+                    _ => Err(StoreError::InvalidXSelection(selection)),
+                },
+                Err(_) => Ok(Selection::Clipboard),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ClipboardDuration {
+    Automatic,
+    Manual(Duration),
+}
+
+impl From<Duration> for ClipboardDuration {
+    fn from(duration: Duration) -> ClipboardDuration {
+        ClipboardDuration::Manual(duration)
+    }
+}
+
+impl ClipboardDuration {
+    fn resolve(&self) -> Result<Duration, StoreError> {
+        match self {
+            ClipboardDuration::Manual(duration) => Ok(*duration),
+            ClipboardDuration::Automatic => match env::var("PASSWORD_STORE_CLIP_TIME") {
+                Ok(seconds) => seconds
+                    .parse()
+                    .map(Duration::from_secs)
+                    .map_err(|_| StoreError::InvalidClipTime(seconds)),
+                Err(_) => Ok(Duration::from_secs(45)),
+            },
+        }
+    }
+}
+
+pub trait ClipboardBackend: Send + Sync {
+    fn get(&self, selection: Selection) -> io::Result<Option<String>>;
+    fn set(&self, selection: Selection, content: &str) -> io::Result<()>;
+    fn clear(&self, selection: Selection) -> io::Result<()>;
+}
+
+// uses the same tools as upstream pass: wl-clipboard on wayland, xclip otherwise
+#[derive(Debug, Clone, Default)]
+pub struct SystemClipboard;
+
+impl SystemClipboard {
+    fn command(&self, selection: Selection, paste: bool) -> io::Result<Command> {
+        if env::var_os("WAYLAND_DISPLAY").is_some() {
+            let mut command = Command::new(if paste { "wl-paste" } else { "wl-copy" });
+            if paste {
+                command.arg("--no-newline");
+            }
+            match selection {
+                Selection::Clipboard => {}
+                Selection::Primary => {
+                    command.arg("--primary");
+                }
+                Selection::Secondary => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        "The secondary selection is not available on wayland",
+                    ));
+                }
+            }
+            Ok(command)
+        } else {
+            let mut command = Command::new("xclip");
+            if paste {
+                command.arg("-o");
+            }
+            command.args(&["-selection", selection.name()]);
+            Ok(command)
+        }
+    }
+}
+
+impl ClipboardBackend for SystemClipboard {
+    fn get(&self, selection: Selection) -> io::Result<Option<String>> {
+        let output = self
+            .command(selection, true)?
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()?;
+        if !output.status.success() {
+            // both tools fail on an empty selection
+            return Ok(None);
+        }
+
+        Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned()))
+    }
+
+    fn set(&self, selection: Selection, content: &str) -> io::Result<()> {
+        let mut child = self
+            .command(selection, false)?
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        child
+            .stdin
+            .take()
+            .expect("stdin of clipboard command not captured")
+            .write_all(content.as_bytes())?;
+
+        if child.wait()?.success() {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("Failed to write to the {} selection", selection.name()),
+            ))
+        }
+    }
+
+    fn clear(&self, selection: Selection) -> io::Result<()> {
+        self.set(selection, "")
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MemoryClipboard {
+    selections: Arc<Mutex<HashMap<Selection, String>>>,
+}
+
+impl MemoryClipboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn content(&self, selection: Selection) -> Option<String> {
+        self.selections
+            .lock()
+            .expect("Memory clipboard poisoned")
+            .get(&selection)
+            .cloned()
+    }
+}
+
+impl ClipboardBackend for MemoryClipboard {
+    fn get(&self, selection: Selection) -> io::Result<Option<String>> {
+        Ok(self.content(selection))
+    }
+
+    fn set(&self, selection: Selection, content: &str) -> io::Result<()> {
+        self.selections
+            .lock()
+            .expect("Memory clipboard poisoned")
+            .insert(selection, content.to_owned());
+        Ok(())
+    }
+
+    fn clear(&self, selection: Selection) -> io::Result<()> {
+        self.selections
+            .lock()
+            .expect("Memory clipboard poisoned")
+            .remove(&selection);
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct Clipboard {
+    backend: Arc<dyn ClipboardBackend>,
+    duration: ClipboardDuration,
+    selection: XSelection,
+}
+
+impl Debug for Clipboard {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Clipboard")
+            .field("backend", &String::from("dyn ClipboardBackend"))
+            .field("duration", &self.duration)
+            .field("selection", &self.selection)
+            .finish()
+    }
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self::new(SystemClipboard)
+    }
+}
+
+impl Clipboard {
+    pub fn new<B: ClipboardBackend + 'static>(backend: B) -> Self {
+        Self {
+            backend: Arc::new(backend),
+            duration: ClipboardDuration::Automatic,
+            selection: XSelection::Automatic,
+        }
+    }
+
+    pub fn duration<D>(&mut self, duration: D) -> &mut Self
+    where
+        D: Into<ClipboardDuration>,
+    {
+        self.duration = duration.into();
+        self
+    }
+
+    pub fn selection<S>(&mut self, selection: S) -> &mut Self
+    where
+        S: Into<XSelection>,
+    {
+        self.selection = selection.into();
+        self
+    }
+
+    pub(crate) fn copy(&self, content: &str) -> Result<ClipboardGuard, StoreError> {
+        let selection = self.selection.resolve()?;
+        let duration = self.duration.resolve()?;
+        let backend = self.backend.clone();
+
+        let previous = backend
+            .get(selection)
+            .map_err(|err| StoreError::Clipboard(selection.name().to_owned(), err))?;
+        backend
+            .set(selection, content)
+            .map_err(|err| StoreError::Clipboard(selection.name().to_owned(), err))?;
+
+        let secret = content.to_owned();
+        let (restore, restore_requested) = mpsc::channel();
+        let started = Instant::now();
+        let handle = thread::spawn(move || {
+            // a dropped guard must not restore early, only an explicit request
+            if let Err(RecvTimeoutError::Disconnected) = restore_requested.recv_timeout(duration) {
+                thread::sleep(duration.checked_sub(started.elapsed()).unwrap_or_default());
+            }
+
+            // leave the clipboard alone if something else was copied meanwhile
+            if backend.get(selection)?.as_deref() != Some(secret.as_str()) {
+                return Ok(());
+            }
+            match previous {
+                Some(previous) => backend.set(selection, &previous),
+                None => backend.clear(selection),
+            }
+        });
+
+        Ok(ClipboardGuard {
+            selection,
+            duration,
+            restore,
+            handle,
+        })
+    }
+}
+
+// the clipboard is restored by a thread of this process, unlike pass it does not outlive it:
+// if the process exits before the timeout expires the secret stays in the clipboard. dropping
+// the guard keeps the timeout running, call `wait` or `restore` before exiting
+#[derive(Debug)]
+pub struct ClipboardGuard {
+    selection: Selection,
+    duration: Duration,
+    restore: Sender<()>,
+    handle: JoinHandle<io::Result<()>>,
+}
+
+impl ClipboardGuard {
+    pub fn selection(&self) -> Selection {
+        self.selection
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    pub fn restore(self) -> Result<(), StoreError> {
+        // the receiver is gone if the timeout already expired
+        let _ = self.restore.send(());
+        self.wait()
+    }
+
+    pub fn wait(self) -> Result<(), StoreError> {
+        let selection = self.selection;
+        self.handle
+            .join()
+            .expect("Clipboard restoration panicked")
+            .map_err(|err| StoreError::Clipboard(selection.name().to_owned(), err))
+    }
+}
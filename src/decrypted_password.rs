@@ -1,10 +1,11 @@
-use crate::{IntoStoreError, Store, StoreError};
+use crate::{Clipboard, ClipboardGuard, IntoStoreError, PassphraseProvider, Store, StoreError};
 use gpgme::{Context, Key, Protocol};
 use std::{
-    fmt,
-    fs::{File, OpenOptions},
+    fmt, fs,
+    fs::{File, OpenOptions, Permissions},
     io::Read,
     io::Write,
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
     path::{Path, PathBuf},
 };
 use tempfile::NamedTempFile;
@@ -12,6 +13,9 @@ use tempfile::NamedTempFile;
 #[cfg(feature = "passphrase-utils")]
 use crate::passphrase_utils::{AnalyzedPassphrase, PassphraseGenerator};
 
+#[cfg(feature = "qr-codes")]
+use crate::QrCode;
+
 pub(crate) fn pw_name(path: &Path, store: &Store) -> String {
     path.strip_prefix(store.location())
         .expect("Password not stored inside this password store!")
@@ -20,10 +24,106 @@ pub(crate) fn pw_name(path: &Path, store: &Store) -> String {
         .to_string()
 }
 
-pub(crate) fn search_gpg_ids(mut path: &Path, ctx: &mut Context) -> Result<Vec<Key>, StoreError> {
+fn signing_fingerprints(
+    signing_keys: &[String],
+    ctx: &mut Context,
+) -> Result<Vec<String>, StoreError> {
+    let mut fingerprints = Vec::new();
+    for id in signing_keys {
+        let key = ctx.get_key(id).with_store_error("Signing key not found")?;
+        if let Ok(fingerprint) = key.fingerprint() {
+            fingerprints.push(fingerprint.to_owned());
+        }
+    }
+
+    Ok(fingerprints)
+}
+
+// without configured signing keys every .gpg-id is trusted, like upstream pass
+pub(crate) fn verify_gpg_id(
+    gpg_id: &Path,
+    signing_keys: &[String],
+    ctx: &mut Context,
+) -> Result<(), StoreError> {
+    if signing_keys.is_empty() {
+        return Ok(());
+    }
+
+    let signature = gpg_id.with_file_name(".gpg-id.sig");
+    let signature = fs::read(&signature)
+        .map_err(|_| StoreError::InvalidGpgIdSignature(gpg_id.to_owned()))?;
+    let content = fs::read(gpg_id).with_store_error(gpg_id.display().to_string())?;
+    let result = ctx
+        .verify_detached(&signature, &content)
+        .map_err(|_| StoreError::InvalidGpgIdSignature(gpg_id.to_owned()))?;
+
+    // compare primary key fingerprints, as the signature may stem from a subkey
+    let allowed = signing_fingerprints(signing_keys, ctx)?;
+    let valid = result.signatures().any(|signature| {
+        signature.status().is_ok()
+            && signature
+                .fingerprint()
+                .ok()
+                .and_then(|fingerprint| ctx.get_key(fingerprint).ok())
+                .and_then(|key| key.fingerprint().ok().map(str::to_owned))
+                .map_or(false, |fingerprint| allowed.contains(&fingerprint))
+    });
+    if !valid {
+        return Err(StoreError::InvalidGpgIdSignature(gpg_id.to_owned()));
+    }
+
+    Ok(())
+}
+
+pub(crate) fn sign_gpg_id(
+    gpg_id: &Path,
+    signing_keys: &[String],
+    umask: u32,
+    passphrase_provider: &PassphraseProvider,
+) -> Result<Option<PathBuf>, StoreError> {
+    if signing_keys.is_empty() {
+        return Ok(None);
+    }
+
+    let mut ctx = passphrase_provider
+        .context()
+        .with_store_error("creating OpenPGP context")?;
+    for id in signing_keys {
+        let key = ctx
+            .get_secret_key(id)
+            .with_store_error("Signing key not found")?;
+        ctx.add_signer(&key).with_store_error("Signing key not usable")?;
+    }
+
+    let content = fs::read(gpg_id).with_store_error(gpg_id.display().to_string())?;
+    let mut signature = Vec::new();
+    passphrase_provider
+        .provide(&mut ctx, |ctx| ctx.sign_detached(&content, &mut signature))
+        .with_store_error(gpg_id.display().to_string())?;
+
+    let path = gpg_id.with_file_name(".gpg-id.sig");
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o666 & !umask)
+        .open(&path)
+        .with_store_error(path.display().to_string())?;
+    file.write_all(&signature)
+        .with_store_error(path.display().to_string())?;
+
+    Ok(Some(path))
+}
+
+pub(crate) fn search_gpg_ids(
+    mut path: &Path,
+    ctx: &mut Context,
+    signing_keys: &[String],
+) -> Result<Vec<Key>, StoreError> {
     let original_path = path.to_owned();
     loop {
         if path.is_dir() && path.join(".gpg-id").is_file() {
+            verify_gpg_id(&path.join(".gpg-id"), signing_keys, ctx)?;
             let mut file = OpenOptions::new()
                 .read(true)
                 .open(path.join(".gpg-id"))
@@ -47,21 +147,58 @@ pub(crate) fn search_gpg_ids(mut path: &Path, ctx: &mut Context) -> Result<Vec<K
     }
 }
 
-pub(crate) fn save_password_to_file(
-    store: &mut Store,
+pub(crate) fn search_gpg_fingerprints(
     path: &Path,
-    password: impl fmt::Display,
-    summary: Option<String>,
-    changes: Vec<String>,
-) -> Result<(), StoreError> {
-    let mut f = NamedTempFile::new_in(path.parent().unwrap())
+    ctx: &mut Context,
+    signing_keys: &[String],
+) -> Result<Vec<String>, StoreError> {
+    let mut fingerprints = search_gpg_ids(path, ctx, signing_keys)?
+        .iter()
+        .filter_map(|key| key.fingerprint().ok().map(|fpr| fpr.to_owned()))
+        .collect::<Vec<_>>();
+    fingerprints.sort();
+
+    Ok(fingerprints)
+}
+
+pub(crate) fn decrypt_password_file(
+    path: &Path,
+    passphrase_provider: &PassphraseProvider,
+) -> Result<Vec<u8>, StoreError> {
+    let mut encrypted = Vec::new();
+    File::open(path)
+        .and_then(|mut pw| pw.read_to_end(&mut encrypted))
         .with_store_error(path.display().to_string())?;
 
+    decrypt_password_data(&encrypted, path, passphrase_provider)
+}
+
+// `path` is only used for error messages, e.g. when the data comes from an old git revision
+pub(crate) fn decrypt_password_data(
+    encrypted: &[u8],
+    path: &Path,
+    passphrase_provider: &PassphraseProvider,
+) -> Result<Vec<u8>, StoreError> {
+    let mut ctx = passphrase_provider
+        .context()
+        .with_store_error("creating OpenPGP context")?;
+    let mut content = Vec::new();
+    passphrase_provider
+        .provide(&mut ctx, |ctx| ctx.decrypt(encrypted, &mut content))
+        .with_store_error(path.display().to_string())?;
+
+    Ok(content)
+}
+
+pub(crate) fn encrypt_password(
+    path: &Path,
+    content: &[u8],
+    signing_keys: &[String],
+) -> Result<Vec<u8>, StoreError> {
     let mut ctx =
         Context::from_protocol(Protocol::OpenPgp).with_store_error(path.display().to_string())?;
-    let content = format!("{}", password);
     let mut encrypted = Vec::new();
-    let gpg_ids = search_gpg_ids(path, &mut ctx)?;
+    let gpg_ids = search_gpg_ids(path, &mut ctx, signing_keys)?;
     let result = ctx
         .encrypt(gpg_ids.iter(), content, &mut encrypted)
         .with_store_error(path.display().to_string())?;
@@ -78,13 +215,49 @@ pub(crate) fn save_password_to_file(
         ));
     }
 
-    f.write_all(&encrypted)
+    Ok(encrypted)
+}
+
+pub(crate) fn write_encrypted_password(
+    path: &Path,
+    encrypted: &[u8],
+    umask: u32,
+) -> Result<(), StoreError> {
+    let mut f = NamedTempFile::new_in(path.parent().unwrap())
+        .with_store_error(path.display().to_string())?;
+
+    f.write_all(encrypted)
         .with_store_error(path.display().to_string())?;
     f.flush().with_store_error(path.display().to_string())?;
+    f.as_file()
+        .set_permissions(Permissions::from_mode(0o666 & !umask))
+        .with_store_error(path.display().to_string())?;
 
     f.persist(path)
         .with_store_error(path.display().to_string())?;
 
+    Ok(())
+}
+
+pub(crate) fn save_password_to_file(
+    store: &mut Store,
+    path: &Path,
+    password: impl fmt::Display,
+    summary: Option<String>,
+    changes: Vec<String>,
+) -> Result<(), StoreError> {
+    let content = format!("{}", password);
+    let encrypted = encrypt_password(path, content.as_bytes(), store.signing_keys())?;
+    write_encrypted_password(path, &encrypted, store.umask())?;
+    // the password is saved already, a stale search index must not turn that into a failure
+    let updated = store
+        .search_index()
+        .map_or(Ok(()), |index| index.update(path, &content, &encrypted));
+    if let Err(err) = updated {
+        store.push_error(err);
+    }
+    store.flush_search_index();
+
     let pw_name = pw_name(path, store);
     if let Some(git) = store.git() {
         // this store uses git
@@ -127,25 +300,34 @@ impl fmt::Display for DecryptedPassword {
 }
 
 impl DecryptedPassword {
-    pub(crate) fn from_path(path: &Path) -> Result<Self, StoreError> {
-        let mut pw = File::open(path).with_store_error(path.display().to_string())?;
-        let mut ctx = Context::from_protocol(Protocol::OpenPgp)
-            .with_store_error("creating OpenPGP context")?;
-        let mut content = Vec::new();
-        // TODO: Add passphrase provider
-        ctx.decrypt(&mut pw, &mut content)
-            .with_store_error(path.display().to_string())?;
-
-        let lines = String::from_utf8_lossy(&content)
+    pub(crate) fn from_path(
+        path: &Path,
+        passphrase_provider: &PassphraseProvider,
+    ) -> Result<Self, StoreError> {
+        let content = decrypt_password_file(path, passphrase_provider)?;
+        Ok(Self::from_content(&content, path))
+    }
+
+    pub(crate) fn from_encrypted(
+        encrypted: &[u8],
+        path: &Path,
+        passphrase_provider: &PassphraseProvider,
+    ) -> Result<Self, StoreError> {
+        let content = decrypt_password_data(encrypted, path, passphrase_provider)?;
+        Ok(Self::from_content(&content, path))
+    }
+
+    fn from_content(content: &[u8], path: &Path) -> Self {
+        let lines = String::from_utf8_lossy(content)
             .lines()
             .map(|line| line.to_owned())
             .collect::<Vec<String>>();
 
-        Ok(Self {
+        Self {
             lines,
             path: path.to_owned(),
             changes: Vec::new(),
-        })
+        }
     }
 
     pub(crate) fn create_and_write(
@@ -225,6 +407,47 @@ impl DecryptedPassword {
         self.lines.iter().map(|line| line.as_str())
     }
 
+    pub fn copy_passphrase(&self, clipboard: &Clipboard) -> Result<ClipboardGuard, StoreError> {
+        let passphrase = self
+            .passphrase()
+            .ok_or_else(|| StoreError::NothingToCopy("passphrase".to_owned()))?;
+        clipboard.copy(passphrase)
+    }
+
+    pub fn copy_line(
+        &self,
+        clipboard: &Clipboard,
+        position: Position,
+    ) -> Result<ClipboardGuard, StoreError> {
+        let line = self
+            .lines
+            .get(position)
+            .ok_or_else(|| StoreError::NothingToCopy(format!("line {}", position)))?;
+        clipboard.copy(line)
+    }
+
+    #[cfg(feature = "qr-codes")]
+    pub fn qr_code(&self) -> Result<QrCode, StoreError> {
+        QrCode::new(self.to_string())
+    }
+
+    #[cfg(feature = "qr-codes")]
+    pub fn passphrase_qr_code(&self) -> Result<QrCode, StoreError> {
+        let passphrase = self
+            .passphrase()
+            .ok_or_else(|| StoreError::NothingToEncode("passphrase".to_owned()))?;
+        QrCode::new(passphrase)
+    }
+
+    #[cfg(feature = "qr-codes")]
+    pub fn line_qr_code(&self, position: Position) -> Result<QrCode, StoreError> {
+        let line = self
+            .lines
+            .get(position)
+            .ok_or_else(|| StoreError::NothingToEncode(format!("line {}", position)))?;
+        QrCode::new(line)
+    }
+
     pub fn set_lines<L: Into<Vec<String>>>(
         &mut self,
         store: &mut Store,
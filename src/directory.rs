@@ -4,12 +4,14 @@ use id_tree::{NodeId, RemoveBehavior};
 use std::{
     fs::{self, OpenOptions},
     io::{Read, Write},
+    os::unix::fs::OpenOptionsExt,
     path::{Path, PathBuf},
 };
 
 use crate::{
     DirectoryInserter, IntoStoreError, Entry, MutEntry, PassNode, PasswordInserter, Store, StoreError,
-    Traversal, TraversalOrder, search_gpg_ids,
+    Traversal, TraversalOrder, decrypt_files, reencrypt_files, search_gpg_ids, sign_gpg_id,
+    verify_gpg_id,
 };
 
 #[derive(Debug, Clone)]
@@ -59,12 +61,13 @@ pub struct Directory {
     root: PathBuf,
 }
 
-fn get_gpg_ids_for_path(path: &Path) -> Result<Vec<GpgKeyId>, StoreError> {
+fn get_gpg_ids_for_path(path: &Path, signing_keys: &[String]) -> Result<Vec<GpgKeyId>, StoreError> {
     let mut ctx = Context::from_protocol(Protocol::OpenPgp)
         .with_store_error("creating OpenPGP context")?;
 
     let gpg_id = path.join(".gpg-id");
     if gpg_id.is_file() {
+        verify_gpg_id(&gpg_id, signing_keys, &mut ctx)?;
         let mut file = OpenOptions::new()
             .read(true)
             .open(&gpg_id)
@@ -109,7 +112,7 @@ impl Directory {
         name: N,
     ) -> crate::parsed::PasswordInserter {
         let name = name.into();
-        let path = self.path.join(&name);
+        let path = self.path.join(format!("{}.gpg", name));
         crate::parsed::PasswordInserter::new(self.node_id.clone(), path, name)
     }
 
@@ -127,8 +130,14 @@ impl Directory {
         &self.path
     }
 
-    pub fn gpg_ids(&self) -> Result<Vec<GpgKeyId>, StoreError> {
-        get_gpg_ids_for_path(self.path.as_path())
+    // fails if the store has signing keys and none of them signed the .gpg-id
+    pub fn gpg_ids(&self, store: &Store) -> Result<Vec<GpgKeyId>, StoreError> {
+        get_gpg_ids_for_path(self.path.as_path(), store.signing_keys())
+    }
+
+    // trusts the .gpg-id as is, even if its signature is missing or invalid
+    pub fn unverified_gpg_ids(&self) -> Result<Vec<GpgKeyId>, StoreError> {
+        get_gpg_ids_for_path(self.path.as_path(), &[])
     }
 
     pub fn parent(self, store: &Store) -> Option<Directory> {
@@ -197,6 +206,9 @@ impl<'a> MutDirectory<'a> {
             if !gpg_ids.is_empty() {
                 let mut file = OpenOptions::new()
                     .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .mode(0o666 & !store.umask())
                     .open(&gpg_id)
                     .with_store_error(gpg_id.display().to_string())?;
                 for key in gpg_ids {
@@ -204,10 +216,20 @@ impl<'a> MutDirectory<'a> {
                         .with_store_error(gpg_id.display().to_string())?;
                 }
                 drop(file);
+                let signature = sign_gpg_id(
+                    &gpg_id,
+                    store.signing_keys(),
+                    store.umask(),
+                    store.passphrase_provider(),
+                )?;
 
                 if let Some(git) = store.git() {
                     git.add(&[&gpg_id])
                         .with_store_error("failed to add .gpg-id edit to git")?;
+                    if let Some(signature) = &signature {
+                        git.add(&[signature])
+                            .with_store_error("failed to add .gpg-id.sig edit to git")?;
+                    }
                     let name =  path.strip_prefix(&root)
                         .unwrap()
                         .with_extension("")
@@ -231,7 +253,7 @@ impl<'a> MutDirectory<'a> {
                     .with_store_error("creating OpenPGP context")?;
                 let parent = path.parent().unwrap();
                 if parent.starts_with(&root) {
-                    let parent_gpg_ids = search_gpg_ids(parent, &mut ctx)?;
+                    let parent_gpg_ids = search_gpg_ids(parent, &mut ctx, store.signing_keys())?;
 
                     if parent_gpg_ids.is_empty() {
                         return Err(
@@ -241,10 +263,20 @@ impl<'a> MutDirectory<'a> {
 
                     fs::remove_file(&gpg_id)
                         .with_store_error("Could not remove gpg-id file")?;
+                    let signature = gpg_id.with_file_name(".gpg-id.sig");
+                    let signed = signature.exists();
+                    if signed {
+                        fs::remove_file(&signature)
+                            .with_store_error("Could not remove gpg-id signature file")?;
+                    }
 
                     if let Some(git) = store.git() {
                         git.add(&[&gpg_id])
                             .with_store_error("failed to add .gpg-id removal to git")?;
+                        if signed {
+                            git.add(&[&signature])
+                                .with_store_error("failed to add .gpg-id.sig removal to git")?;
+                        }
                         let name =  path.strip_prefix(&root)
                             .unwrap()
                             .with_extension("")
@@ -271,47 +303,66 @@ impl<'a> MutDirectory<'a> {
             Ok(())
         };
 
-        let steps = (|| {
-            write_gpg_ids(&gpg_ids, self.store)?;
-
-            let joined_ids = gpg_ids.iter()
-                .map(GpgKeyId::id)
-                .collect::<Vec<_>>()
-                .join(", ");
-            let passwords = self.store
-                .show(self.path(), TraversalOrder::PreOrder)?
+        let joined_ids = gpg_ids.iter()
+            .map(GpgKeyId::id)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let name = path.strip_prefix(&root)
+            .unwrap()
+            .display()
+            .to_string();
+        let summary = if name.is_empty() {
+            format!("Reencrypt store as gpg-ids changed to {}.", joined_ids)
+        } else {
+            format!("Reencrypt '{}' as gpg-ids changed to {}.", name, joined_ids)
+        };
+        let steps = self.store.batch(summary, |store| {
+            let passwords = store
+                .show(&path, TraversalOrder::PreOrder)?
                 .filter_map(Entry::password)
+                .map(|password| password.path().to_owned())
                 .collect::<Vec<_>>();
-            for password in &passwords {
-                password.decrypt()?
-                    .save(
-                        Some(format!(
-                            "Reencrypt '{}' as gpg-ids changed to {}.",
-                            password.path()
-                                .strip_prefix(&root)
-                                .unwrap()
-                                .with_extension("")
-                                .display(),
-                            joined_ids,
-                        )),
-                        self.store,
-                    )?;
+            let decrypted = decrypt_files(store, &passwords)?;
+
+            write_gpg_ids(&gpg_ids, store)?;
+            let reencrypted = reencrypt_files(store, decrypted)?;
+
+            if let Some(git) = store.git() {
+                let paths = reencrypted.iter().map(PathBuf::as_path).collect::<Vec<_>>();
+                git.add(&paths)
+                    .with_store_error("failed to add reencrypted passwords to git")?;
+                let message = reencrypted.iter()
+                    .map(|path| format!(
+                        "Reencrypt '{}' as gpg-ids changed to {}.",
+                        path.strip_prefix(&root)
+                            .unwrap()
+                            .with_extension("")
+                            .display(),
+                        joined_ids,
+                    ))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                git.commit(message)
+                    .with_store_error("failed to commit reencrypted passwords to git")?;
             }
 
             Ok(())
-        })();
+        });
 
+        // a batch only rolls back with git and a clean repository, reencrypted passwords are
+        // restored either way
         if steps.is_err() {
-            // TODO: Reencryption might have already reencrypted some passwords.
-            //       What to do now?
-            write_gpg_ids(&old_gpg_ids, self.store)?;
+            let current = self.gpg_ids().unwrap_or_default();
+            if current.iter().map(GpgKeyId::id).ne(old_gpg_ids.iter().map(GpgKeyId::id)) {
+                write_gpg_ids(&old_gpg_ids, self.store)?;
+            }
         }
 
         steps
     }
 
     pub fn gpg_ids(&self) -> Result<Vec<GpgKeyId>, StoreError> {
-        get_gpg_ids_for_path(self.path())
+        get_gpg_ids_for_path(self.path(), self.store.signing_keys())
     }
 
     pub fn add_gpg_id(&mut self, gpg_key: GpgKeyId) -> Result<(), StoreError> {
@@ -381,11 +432,11 @@ impl<'a> MutDirectory<'a> {
         self.to_entry().rename(name)
     }
 
-    pub fn move_to(&mut self, directory: &Directory) {
+    pub fn move_to(&mut self, directory: &Directory) -> Result<(), StoreError> {
         self.to_entry().move_to(directory)
     }
 
-    pub fn copy_to(&mut self, directory: &Directory) {
+    pub fn copy_to(&mut self, directory: &Directory) -> Result<(), StoreError> {
         self.to_entry().copy_to(directory)
     }
 
@@ -1,26 +1,40 @@
+use gpgme::{Context, Protocol};
 use id_tree::NodeId;
-use id_tree::RemoveBehavior;
+use id_tree::{InsertBehavior, MoveBehavior, Node, RemoveBehavior};
+use walkdir::WalkDir;
 
+use std::collections::HashMap;
+use std::fs::{DirBuilder, Permissions};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
 use std::path::Path;
 use std::{fmt, path::PathBuf};
 use std::{fs, io};
 
 use crate::{
-    Directory, EntryKind, IntoStoreError, MutDirectory, MutPassword, PassNode, Password, Store, StoreError,
+    decrypt_files, reencrypt_files, search_gpg_fingerprints, Directory, EntryKind,
+    IntoStoreError, MutDirectory, MutPassword, PassNode, PassphraseProvider, Password, Store,
+    StoreError, TraversalOrder,
 };
 
 pub struct Entry {
     data: PassNode,
     node_id: NodeId,
     root: PathBuf,
+    passphrase_provider: PassphraseProvider,
 }
 
 impl Entry {
-    pub(crate) fn new(node_id: NodeId, data: PassNode, root: PathBuf) -> Self {
+    pub(crate) fn new(
+        node_id: NodeId,
+        data: PassNode,
+        root: PathBuf,
+        passphrase_provider: PassphraseProvider,
+    ) -> Self {
         Self {
             data,
             node_id,
             root,
+            passphrase_provider,
         }
     }
 
@@ -42,7 +56,13 @@ impl Entry {
 
     pub fn password(self) -> Option<Password> {
         if let PassNode::Password { name, path } = self.data {
-            Some(Password::new(name, path, self.root, self.node_id))
+            Some(Password::new(
+                name,
+                path,
+                self.root,
+                self.node_id,
+                self.passphrase_provider,
+            ))
         } else {
             None
         }
@@ -185,9 +205,205 @@ impl<'a> MutEntry<'a> {
         Ok(())
     }
 
-    pub fn move_to(&mut self, _directory: &Directory) {}
+    fn transfer_paths(
+        &self,
+        directory: &Directory,
+        operation: &str,
+    ) -> Result<(PathBuf, PathBuf), StoreError> {
+        let old_path = self.path().to_owned();
+        if old_path == *self.store.location() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("Cannot {} store's root directory", operation),
+            ))
+            .with_store_error(format!("Attempted to {} store's root directory", operation))?;
+        }
+        if !directory.path().starts_with(self.store.location()) {
+            return Err(StoreError::NotInStore(directory.path().to_owned()));
+        }
+        let new_path = directory.path().join(
+            old_path
+                .file_name()
+                .expect("Store entry without a file name found"),
+        );
+        if new_path.starts_with(&old_path) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Cannot {} a directory into itself", operation),
+            ))
+            .with_store_error(format!("Attempted to {} directory into itself", operation))?;
+        }
+        if new_path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Target of {} already exists", operation),
+            ))
+            .with_store_error(new_path.display().to_string())?;
+        }
+
+        Ok((old_path, new_path))
+    }
+
+    // remember the recipients of all transferred passwords, so only those
+    // passwords get re-encrypted whose recipients differ at the new location
+    fn password_recipients(
+        &self,
+        old_path: &Path,
+        new_path: &Path,
+        ctx: &mut Context,
+    ) -> Result<Vec<(PathBuf, Vec<String>)>, StoreError> {
+        self.store
+            .show(old_path, TraversalOrder::PreOrder)?
+            .filter_map(Entry::password)
+            .map(|password| {
+                let fingerprints =
+                    search_gpg_fingerprints(password.path(), ctx, self.store.signing_keys())?;
+                Ok((relocate(password.path(), old_path, new_path), fingerprints))
+            })
+            .collect()
+    }
+
+    fn transfer_message(
+        &self,
+        operation: &str,
+        old_path: &Path,
+        new_path: &Path,
+        reencrypted: usize,
+    ) -> String {
+        let root = self.store.location();
+        let message = format!(
+            "{} '{}' to '{}'.",
+            operation,
+            old_path
+                .strip_prefix(root)
+                .unwrap()
+                .with_extension("")
+                .display(),
+            new_path
+                .strip_prefix(root)
+                .unwrap()
+                .with_extension("")
+                .display(),
+        );
+
+        if reencrypted > 0 {
+            format!(
+                "{}\n\nReencrypt {} password(s) for the gpg-ids of the new location.\n",
+                message,
+                reencrypted,
+            )
+        } else {
+            message
+        }
+    }
+
+    pub fn move_to(&mut self, directory: &Directory) -> Result<(), StoreError> {
+        let (old_path, new_path) = self.transfer_paths(directory, "move")?;
+        let mut ctx = Context::from_protocol(Protocol::OpenPgp)
+            .with_store_error("creating OpenPGP context")?;
+        let passwords = self.password_recipients(&old_path, &new_path, &mut ctx)?;
+
+        fs::rename(&old_path, &new_path).with_store_error("Failed to move store entry")?;
+
+        // reencrypted passwords are swapped in all at once, a failure leaves them untouched
+        let reencrypted = match reencrypt_outdated(&passwords, &mut ctx, self.store) {
+            Ok(reencrypted) => reencrypted,
+            Err(err) => {
+                fs::rename(&new_path, &old_path)
+                    .with_store_error("Failed to restore moved store entry")?;
+                return Err(err);
+            }
+        };
+
+        self.store
+            .tree
+            .move_node(&self.node_id, MoveBehavior::ToParent(directory.node_id()))
+            .expect("Could not move entry in internal tree structure");
+        let subtree = self
+            .store
+            .tree
+            .traverse_pre_order_ids(&self.node_id)
+            .expect("Could not traverse moved entry in internal tree structure")
+            .collect::<Vec<_>>();
+        for node_id in subtree {
+            let path = self.store.tree.get_mut(&node_id).unwrap().data_mut().path_mut();
+            *path = relocate(path, &old_path, &new_path);
+        }
+
+        let message = self.transfer_message("Move", &old_path, &new_path, reencrypted.len());
+        if let Some(git) = self.store.git() {
+            git.add(&[&old_path, &new_path])
+                .with_store_error("failed to add move to git")?;
+            git.commit(message)
+                .with_store_error("failed to commit move to git")?;
+        }
+
+        Ok(())
+    }
+
+    pub fn copy_to(&mut self, directory: &Directory) -> Result<(), StoreError> {
+        let (old_path, new_path) = self.transfer_paths(directory, "copy")?;
+        let mut ctx = Context::from_protocol(Protocol::OpenPgp)
+            .with_store_error("creating OpenPGP context")?;
+        let passwords = self.password_recipients(&old_path, &new_path, &mut ctx)?;
+
+        let copied = copy_recursively(&old_path, &new_path, self.store.umask())
+            .and_then(|_| reencrypt_outdated(&passwords, &mut ctx, self.store));
+        let reencrypted = match copied {
+            Ok(reencrypted) => reencrypted,
+            Err(err) => {
+                let cleanup = if new_path.is_dir() {
+                    fs::remove_dir_all(&new_path)
+                } else if new_path.exists() {
+                    fs::remove_file(&new_path)
+                } else {
+                    Ok(())
+                };
+                cleanup.with_store_error("Failed to remove incomplete copy of store entry")?;
+                return Err(err);
+            }
+        };
+
+        let subtree = self
+            .store
+            .tree
+            .traverse_pre_order_ids(&self.node_id)
+            .expect("Could not traverse copied entry in internal tree structure")
+            .collect::<Vec<_>>();
+        let mut copies: HashMap<NodeId, NodeId> = HashMap::new();
+        for node_id in subtree {
+            let node = self.store.tree.get(&node_id).unwrap();
+            let parent_id = if node_id == self.node_id {
+                directory.node_id().clone()
+            } else {
+                let parent = node.parent().expect("Copied entry has no parent");
+                copies
+                    .get(parent)
+                    .expect("Parent of copied entry has not been copied")
+                    .clone()
+            };
+            let mut data = node.data().clone();
+            let path = data.path_mut();
+            *path = relocate(path, &old_path, &new_path);
+
+            let copy_id = self
+                .store
+                .tree
+                .insert(Node::new(data), InsertBehavior::UnderNode(&parent_id))
+                .expect("Parent of copied entry does not exist in internal tree");
+            copies.insert(node_id, copy_id);
+        }
 
-    pub fn copy_to(&mut self, _directory: &Directory) {}
+        let message = self.transfer_message("Copy", &old_path, &new_path, reencrypted.len());
+        if let Some(git) = self.store.git() {
+            git.add(&[&new_path])
+                .with_store_error("failed to add copy to git")?;
+            git.commit(message)
+                .with_store_error("failed to commit copy to git")?;
+        }
+
+        Ok(())
+    }
 
     pub fn mut_password(self) -> Option<MutPassword<'a>> {
         if self.kind() == EntryKind::Password {
@@ -206,6 +422,53 @@ impl<'a> MutEntry<'a> {
     }
 }
 
+// only passwords whose recipients differ at the new location are reencrypted, staged in
+// temporary files like for changed gpg-ids
+fn reencrypt_outdated(
+    passwords: &[(PathBuf, Vec<String>)],
+    ctx: &mut Context,
+    store: &mut Store,
+) -> Result<Vec<PathBuf>, StoreError> {
+    let mut outdated = Vec::new();
+    for (path, fingerprints) in passwords {
+        if search_gpg_fingerprints(path, ctx, store.signing_keys())? != *fingerprints {
+            outdated.push(path.clone());
+        }
+    }
+
+    let decrypted = decrypt_files(store, &outdated)?;
+    reencrypt_files(store, decrypted)
+}
+
+fn copy_recursively(from: &Path, to: &Path, umask: u32) -> Result<(), StoreError> {
+    for entry in WalkDir::new(from) {
+        let entry = entry
+            .map_err(io::Error::from)
+            .with_store_error(from.display().to_string())?;
+        let target = relocate(entry.path(), from, to);
+        if entry.file_type().is_dir() {
+            DirBuilder::new()
+                .mode(0o777 & !umask)
+                .create(&target)
+                .with_store_error(target.display().to_string())?;
+        } else {
+            fs::copy(entry.path(), &target).with_store_error(target.display().to_string())?;
+            fs::set_permissions(&target, Permissions::from_mode(0o666 & !umask))
+                .with_store_error(target.display().to_string())?;
+        }
+    }
+
+    Ok(())
+}
+
+fn relocate(path: &Path, from: &Path, to: &Path) -> PathBuf {
+    match path.strip_prefix(from) {
+        Ok(relative) if relative.as_os_str().is_empty() => to.to_owned(),
+        Ok(relative) => to.join(relative),
+        Err(_) => path.to_owned(),
+    }
+}
+
 impl<'a> fmt::Debug for MutEntry<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let kind = self.kind().fmt(f);
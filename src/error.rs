@@ -32,16 +32,50 @@ pub enum StoreError {
     Io(String, #[source] io::Error),
     #[error("Password store path is not a directory: {0}")]
     NoDirectory(PathBuf),
+    #[error("A password store already exists at {0}")]
+    AlreadyExists(PathBuf),
+    #[error("Invalid umask '{0}', expected an octal number like 077")]
+    InvalidUmask(String),
+    #[error("PASSWORD_STORE_KEY is ignored, overriding GPG IDs from the environment is a security risk")]
+    EnvGpgIdOverride,
+    #[error("PASSWORD_STORE_GENERATED_LENGTH is ignored due to security reasons")]
+    EnvGeneratedLength,
+    #[error("{0} is ignored due to security reasons")]
+    EnvCharacterSet(&'static str),
+    #[error("PASSWORD_STORE_GPG_OPTS is ignored due to security reasons")]
+    EnvGpgOptions,
+    #[error("{0} is ignored as pass extensions are not supported")]
+    EnvExtensions(&'static str),
+    #[error("Invalid clipboard duration '{0}', expected a number of seconds")]
+    InvalidClipTime(String),
+    #[error("Invalid X selection '{0}', expected clipboard, primary or secondary")]
+    InvalidXSelection(String),
+    #[error("Could not access the {0} selection")]
+    Clipboard(String, #[source] io::Error),
+    #[error("Nothing to copy, {0} not found")]
+    NothingToCopy(String),
+    #[error("Invalid search pattern '{0}'")]
+    InvalidPattern(String, #[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error("Could not search password {0}")]
+    Grep(String, #[source] Box<StoreError>),
     #[error("environment variable {0} is not set")]
     EnvVar(String, #[source] env::VarError),
     #[error("Cannot find home directory for current user: {0}")]
     NoHome(String, #[source] Box<StoreError>),
+    #[error("Cannot find a cache directory for the search index")]
+    NoCacheDir,
+    #[error("Search index {0} is corrupted")]
+    InvalidSearchIndex(PathBuf),
+    #[error("No own secret key found to encrypt the search index {0}")]
+    NoSecretKey(PathBuf),
     #[error("Given path is not contained in the password store: {0}")]
     NotInStore(PathBuf),
     #[error("GPG operation failed: {0}")]
     Gpg(String, #[source] gpgme::Error),
     #[error("No GPG IDs found for '{0}' and all its parent directories")]
     NoGpgId(String),
+    #[error("Signature for {0} is missing or was not made by a signing key")]
+    InvalidGpgIdSignature(PathBuf),
     #[error("Invalid passphrase index {0}")]
     PassphraseIndex(usize),
     #[error("Generating passphrase failed: {0}")]
@@ -50,6 +84,14 @@ pub enum StoreError {
     PassphrasePersist(String, #[source] PersistError),
     #[error("The git operation {0} failed")]
     GitError(String, #[source] git2::Error),
+    #[error("The password store does not use git")]
+    NoGit,
+    #[error("{0} does not exist in commit {1}")]
+    NotInRevision(PathBuf, String),
+    #[error("The repository has uncommitted changes, so '{0}' was committed change by change")]
+    BatchNotClean(String),
+    #[error("{0}, rolling back the batch failed as well: {1}")]
+    BatchRollback(#[source] Box<StoreError>, git2::Error),
 
     #[cfg(feature = "parsed-passwords")]
     #[error("Failed to parse password content for {0}")]
@@ -60,6 +102,27 @@ pub enum StoreError {
     #[cfg(feature = "parsed-passwords")]
     #[error("Line at position {0} is not an entry")]
     PasswordLineNotAnEntry(Position),
+    #[cfg(feature = "qr-codes")]
+    #[error("Failed to create QR code")]
+    QrCode(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[cfg(feature = "qr-codes")]
+    #[error("Nothing to encode, {0} not found")]
+    NothingToEncode(String),
+    #[cfg(feature = "otp")]
+    #[error("Invalid OTP URI: {0}")]
+    InvalidOtpUri(String),
+    #[cfg(feature = "otp")]
+    #[error("No OTP URI found in password {0}")]
+    NoOtp(String),
+    #[cfg(feature = "otp")]
+    #[error("OTP URI in password {0} is not time based")]
+    NotTotp(String),
+    #[cfg(feature = "otp")]
+    #[error("OTP URI in password {0} is not counter based")]
+    NotHotp(String),
+    #[cfg(feature = "otp-qr")]
+    #[error("Failed to read QR code from image")]
+    QrImage(#[source] Box<dyn std::error::Error + Send + Sync>),
 }
 
 pub(crate) trait IntoStoreError<T> {
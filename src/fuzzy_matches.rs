@@ -0,0 +1,57 @@
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+
+use crate::{pw_name, Entries, Entry, EntryKind, Store};
+
+#[derive(Debug)]
+pub struct FuzzyMatch {
+    score: i64,
+    name: String,
+    positions: Vec<usize>,
+    entry: Entry,
+}
+
+impl FuzzyMatch {
+    pub fn score(&self) -> i64 {
+        self.score
+    }
+
+    // the password path like pass shows it, e.g. "work/aws/root"
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // character (not byte) indices into the name, ready for highlighting
+    pub fn positions(&self) -> &[usize] {
+        &self.positions
+    }
+
+    pub fn entry(&self) -> &Entry {
+        &self.entry
+    }
+
+    pub fn into_entry(self) -> Entry {
+        self.entry
+    }
+}
+
+pub(crate) fn fuzzy_find(store: &Store, query: &str, entries: Entries) -> Vec<FuzzyMatch> {
+    // smart case like fzf: case-insensitive unless the query contains upper case letters
+    let matcher = SkimMatcherV2::default().smart_case();
+
+    let mut matches = entries
+        .filter(|entry| entry.kind() == EntryKind::Password)
+        .filter_map(|entry| {
+            let name = pw_name(entry.path(), store);
+            let (score, positions) = matcher.fuzzy_indices(&name, query)?;
+            Some(FuzzyMatch {
+                score,
+                name,
+                positions,
+                entry,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    matches.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+    matches
+}
@@ -0,0 +1,82 @@
+use std::{fs, path::Path};
+
+use git2::{ErrorClass, ErrorCode, ObjectType, ResetType, StatusOptions, Statuses};
+
+use super::{Git, GitResult};
+
+fn io_error(err: std::io::Error, path: &Path) -> git2::Error {
+    git2::Error::from_str(&format!("{}: {}", path.display(), err))
+}
+
+impl Git {
+    fn batch_statuses(&self) -> GitResult<Statuses> {
+        let mut opts = StatusOptions::new();
+        opts.include_ignored(false);
+        opts.include_untracked(true);
+        opts.recurse_untracked_dirs(true);
+        opts.exclude_submodules(true);
+        self.repo.statuses(Some(&mut opts))
+    }
+
+    pub(crate) fn in_batch(&self) -> bool {
+        self.batch.is_some()
+    }
+
+    // returns false if a batch is already running, the outer batch then owns all changes
+    pub(crate) fn begin_batch(&mut self) -> GitResult<bool> {
+        if self.batch.is_some() {
+            return Ok(false);
+        }
+        // a rollback resets the whole working tree, so it must not contain foreign changes
+        if !self.batch_statuses()?.is_empty() {
+            return Err(git2::Error::new(
+                ErrorCode::Modified,
+                ErrorClass::Repository,
+                "Repository status is not clean",
+            ));
+        }
+
+        self.batch = Some(Vec::new());
+        Ok(true)
+    }
+
+    pub(crate) fn commit_batch(&mut self, summary: &str) -> GitResult<()> {
+        let messages = self.batch.take().unwrap_or_default();
+        if messages.is_empty() {
+            return Ok(());
+        }
+
+        let mut message = format!("{}\n\n", summary);
+        for change in messages {
+            message.push_str(change.trim_end());
+            message.push('\n');
+        }
+        self.commit(message)
+    }
+
+    pub(crate) fn rollback_batch(&mut self) -> GitResult<()> {
+        self.batch = None;
+        let head = self.repo.head()?.peel(ObjectType::Commit)?;
+        self.repo.reset(&head, ResetType::Hard, None)?;
+
+        // the batch started on a clean working tree, so every untracked file was created by it
+        let workdir = self.repo.workdir().expect("Repository has no workdir").to_owned();
+        let created = self
+            .batch_statuses()?
+            .iter()
+            .filter(|entry| entry.status().is_wt_new())
+            .filter_map(|entry| entry.path().map(|path| workdir.join(path)))
+            .collect::<Vec<_>>();
+        for path in created {
+            fs::remove_file(&path).map_err(|err| io_error(err, &path))?;
+            for dir in path.ancestors().skip(1).take_while(|dir| *dir != workdir) {
+                if fs::read_dir(dir).map_err(|err| io_error(err, dir))?.next().is_some() {
+                    break;
+                }
+                fs::remove_dir(dir).map_err(|err| io_error(err, dir))?;
+            }
+        }
+
+        Ok(())
+    }
+}
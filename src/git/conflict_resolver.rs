@@ -6,6 +6,8 @@ use std::os::unix::ffi::OsStrExt;
 
 use git2::IndexEntry;
 
+use crate::PassphraseProvider;
+
 use super::{GitResult, conflicted_binary::ConflictedBinary, conflicted_gpg_id::ConflictedGpgId, conflicted_password::ConflictedPassword, conflicted_plain_text::ConflictedPlainText};
 
 #[derive(Debug)]
@@ -109,7 +111,7 @@ impl<'a> ConflictResolver<'a> {
         }
     }
 
-    pub(crate) fn from_index(index: git2::Index, repo: &'a git2::Repository, finish_cb: impl FnOnce(&'a git2::Repository, Option<git2::Index>) -> GitResult<()> + 'a) -> GitResult<Self> {
+    pub(crate) fn from_index(index: git2::Index, repo: &'a git2::Repository, passphrase_provider: &PassphraseProvider, finish_cb: impl FnOnce(&'a git2::Repository, Option<git2::Index>) -> GitResult<()> + 'a) -> GitResult<Self> {
         let mut conflicted_passwords = Vec::new();
         let mut conflicted_gpg_ids = Vec::new();
         let mut conflicted_plain_texts = Vec::new();
@@ -127,7 +129,7 @@ impl<'a> ConflictResolver<'a> {
                 entry_has_extension(&our_entry, "gpg") ||
                 entry_has_extension(&their_entry, "gpg")
             {
-                let conflicted_password_res = ConflictedPassword::new(ancestor_entry.clone(), our_entry.clone(), their_entry.clone());
+                let conflicted_password_res = ConflictedPassword::new(ancestor_entry.clone(), our_entry.clone(), their_entry.clone(), passphrase_provider);
                 if conflicted_password_res.is_some() {
                     conflicted_passwords.push(conflicted_password_res.unwrap());
                     continue 'conflicts;
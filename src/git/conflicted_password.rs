@@ -1,6 +1,6 @@
 use std::{fmt, fs::OpenOptions, io::Read, path::{Path, PathBuf}};
 
-use crate::{ConflictResolver, IntoStoreError, Position, StoreError};
+use crate::{ConflictResolver, IntoStoreError, PassphraseProvider, Position, StoreError};
 
 use super::conflict_resolver::ConflictEntry;
 
@@ -63,19 +63,19 @@ pub struct ConflictedPassword {
 }
 
 impl ConflictedPassword {
-    pub(super) fn new(ancestor: Option<ConflictEntry>, our: Option<ConflictEntry>, their: Option<ConflictEntry>) -> Option<Self> {
+    pub(super) fn new(ancestor: Option<ConflictEntry>, our: Option<ConflictEntry>, their: Option<ConflictEntry>, passphrase_provider: &PassphraseProvider) -> Option<Self> {
         let ancestor_password = if let Some(ancestor) = &ancestor {
-            Some(ConflictedDecryptedPassword::from_buffer(&ancestor.content, &ancestor.path).ok()?)
+            Some(ConflictedDecryptedPassword::from_buffer(&ancestor.content, &ancestor.path, passphrase_provider).ok()?)
         } else {
             None
         };
         let our_password = if let Some(our) = &our {
-            Some(ConflictedDecryptedPassword::from_buffer(&our.content, &our.path).ok()?)
+            Some(ConflictedDecryptedPassword::from_buffer(&our.content, &our.path, passphrase_provider).ok()?)
         } else {
             None
         };
         let their_password = if let Some(their) = &their {
-            Some(ConflictedDecryptedPassword::from_buffer(&their.content, &their.path).ok()?)
+            Some(ConflictedDecryptedPassword::from_buffer(&their.content, &their.path, passphrase_provider).ok()?)
         } else {
             None
         };
@@ -213,11 +213,10 @@ impl fmt::Display for ConflictedDecryptedPassword {
 }
 
 impl ConflictedDecryptedPassword {
-    fn from_buffer(content: &[u8], path: &Path) -> gpgme::Result<Self> {
-        let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+    fn from_buffer(content: &[u8], path: &Path, passphrase_provider: &PassphraseProvider) -> gpgme::Result<Self> {
+        let mut ctx = passphrase_provider.context()?;
         let mut decrypted = Vec::new();
-        // TODO: Add passphrase provider
-        ctx.decrypt(content, &mut decrypted)?;
+        passphrase_provider.provide(&mut ctx, |ctx| ctx.decrypt(content, &mut decrypted))?;
         let lines = String::from_utf8_lossy(&decrypted)
             .lines()
             .map(|line| line.to_owned())
@@ -0,0 +1,235 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use git2::{
+    Commit, Delta, DiffFindOptions, ErrorCode, ObjectType, Oid, Sort, Tree, TreeWalkMode,
+    TreeWalkResult,
+};
+
+use super::{Git, GitResult};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Revision {
+    id: Oid,
+    author: String,
+    email: String,
+    time: SystemTime,
+    message: String,
+    path: PathBuf,
+}
+
+impl Revision {
+    fn new(commit: &Commit, path: &Path) -> Self {
+        let author = commit.author();
+        let seconds = author.when().seconds().max(0) as u64;
+
+        Self {
+            id: commit.id(),
+            author: String::from_utf8_lossy(author.name_bytes()).into_owned(),
+            email: String::from_utf8_lossy(author.email_bytes()).into_owned(),
+            time: UNIX_EPOCH + Duration::from_secs(seconds),
+            message: String::from_utf8_lossy(commit.message_bytes()).into_owned(),
+            path: path.to_owned(),
+        }
+    }
+
+    pub fn id(&self) -> Oid {
+        self.id
+    }
+
+    pub fn author(&self) -> &str {
+        &self.author
+    }
+
+    pub fn email(&self) -> &str {
+        &self.email
+    }
+
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn summary(&self) -> &str {
+        self.message.lines().next().unwrap_or_default()
+    }
+
+    // relative to the store, differs from the current location if the entry was renamed since
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+fn entry_id(tree: &Tree, path: &Path) -> Option<Oid> {
+    tree.get_path(path).ok().map(|entry| entry.id())
+}
+
+impl Git {
+    // walks every ancestor of HEAD, including commits merged in by a pull, and tracks the path
+    // of the file across renames on each branch, `visit` receives every commit with the path
+    // the file had in it and whether it was touched
+    fn follow<F>(&self, path: &Path, mut visit: F) -> GitResult<()>
+    where
+        F: FnMut(&Commit, &Path, bool) -> bool,
+    {
+        let head = match self.repo.head() {
+            Ok(head) => head.peel_to_commit()?.id(),
+            // unborn branch, there is no history yet
+            Err(_) => return Ok(()),
+        };
+
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.push(head)?;
+        revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+
+        // children come before their parents, so every path is known once its commit is visited
+        let mut paths = HashMap::new();
+        paths.insert(head, path.to_owned());
+        for id in revwalk {
+            let id = id?;
+            let path = paths.remove(&id).expect("Commit visited before its children");
+            let commit = self.repo.find_commit(id)?;
+            let tree = commit.tree()?;
+            let current = entry_id(&tree, &path);
+
+            // like git log, a merge only touched the file if it differs from every parent
+            let mut touched = current.is_some() || commit.parent_count() > 0;
+            for parent in commit.parents() {
+                let parent_tree = parent.tree()?;
+                let previous = entry_id(&parent_tree, &path);
+                touched &= current != previous;
+                let parent_path = match (current, previous) {
+                    (Some(_), None) => self
+                        .renamed_from(&parent_tree, &tree, &path)?
+                        .unwrap_or_else(|| path.clone()),
+                    _ => path.clone(),
+                };
+                paths.entry(parent.id()).or_insert(parent_path);
+            }
+
+            if !visit(&commit, &path, touched) {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn renamed_from(&self, old: &Tree, new: &Tree, path: &Path) -> GitResult<Option<PathBuf>> {
+        let mut diff = self.repo.diff_tree_to_tree(Some(old), Some(new), None)?;
+        diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+
+        let old_path = diff
+            .deltas()
+            .find(|delta| {
+                delta.status() == Delta::Renamed && delta.new_file().path() == Some(path)
+            })
+            .and_then(|delta| delta.old_file().path().map(Path::to_owned));
+        Ok(old_path)
+    }
+
+    pub(crate) fn history(&self, path: &Path) -> GitResult<Vec<Revision>> {
+        let mut revisions = Vec::new();
+        self.follow(path, |commit, path, touched| {
+            if touched {
+                revisions.push(Revision::new(commit, path));
+            }
+            true
+        })?;
+
+        Ok(revisions)
+    }
+
+    // `None` if the commit is not an ancestor of HEAD
+    pub(crate) fn path_at(&self, path: &Path, commit: Oid) -> GitResult<Option<PathBuf>> {
+        let mut found = None;
+        self.follow(path, |visited, path, _| {
+            if visited.id() == commit {
+                found = Some(path.to_owned());
+                false
+            } else {
+                true
+            }
+        })?;
+
+        Ok(found)
+    }
+
+    pub(crate) fn blob_at(&self, commit: Oid, path: &Path) -> GitResult<Option<Vec<u8>>> {
+        let tree = self.repo.find_commit(commit)?.tree()?;
+        let entry = match tree.get_path(path) {
+            Ok(entry) => entry,
+            Err(err) if err.code() == ErrorCode::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        Ok(Some(self.repo.find_blob(entry.id())?.content().to_vec()))
+    }
+
+    // the file itself or every file of the directory, paths are relative to the store
+    pub(crate) fn files_at(
+        &self,
+        commit: Oid,
+        path: &Path,
+    ) -> GitResult<Vec<(PathBuf, Vec<u8>)>> {
+        let tree = self.repo.find_commit(commit)?.tree()?;
+        let entry = match tree.get_path(path) {
+            Ok(entry) => entry,
+            Err(err) if err.code() == ErrorCode::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+
+        match entry.kind() {
+            Some(ObjectType::Blob) => {
+                let blob = self.repo.find_blob(entry.id())?;
+                Ok(vec![(path.to_owned(), blob.content().to_vec())])
+            }
+            Some(ObjectType::Tree) => {
+                let subtree = self.repo.find_tree(entry.id())?;
+                let mut files = Vec::new();
+                let mut result = Ok(());
+                let walked = subtree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+                    if entry.kind() != Some(ObjectType::Blob) {
+                        return TreeWalkResult::Ok;
+                    }
+                    match self.repo.find_blob(entry.id()) {
+                        Ok(blob) => {
+                            let name = OsStr::from_bytes(entry.name_bytes());
+                            files.push((path.join(dir).join(name), blob.content().to_vec()));
+                            TreeWalkResult::Ok
+                        }
+                        Err(err) => {
+                            result = Err(err);
+                            TreeWalkResult::Abort
+                        }
+                    }
+                });
+                // the error of a failed blob lookup is more helpful than the aborted walk
+                result?;
+                walked?;
+
+                Ok(files)
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    // the .gpg-id which was responsible for `path` in the given commit
+    pub(crate) fn gpg_id_at(&self, commit: Oid, path: &Path) -> GitResult<Option<Vec<u8>>> {
+        for dir in path.ancestors().skip(1) {
+            if let Some(gpg_id) = self.blob_at(commit, &dir.join(".gpg-id"))? {
+                return Ok(Some(gpg_id));
+            }
+        }
+
+        Ok(None)
+    }
+}
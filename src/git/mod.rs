@@ -3,21 +3,28 @@ mod conflicted_password;
 mod conflicted_gpg_id;
 mod conflicted_plain_text;
 mod conflicted_binary;
+mod batch;
+mod history;
+mod progress;
+mod signing;
 
 pub use conflict_resolver::*;
 pub use conflicted_password::*;
 pub use conflicted_gpg_id::*;
 pub use conflicted_plain_text::*;
 pub use conflicted_binary::*;
+pub use history::*;
+pub use progress::*;
+pub use signing::*;
 
 use std::{fmt, path::PathBuf};
 use std::path::Path;
 
 pub use conflict_resolver::ConflictResolver;
-use crate::try_or;
+use crate::{try_or, CredentialsProvider, PassphraseProvider};
 
 use custom_debug::Debug;
-use git2::{AnnotatedCommit, AutotagOption, BranchType, Config, ErrorClass, ErrorCode, FetchOptions, IndexAddOption, ObjectType, Reference, Repository, StatusOptions, build::CheckoutBuilder};
+use git2::{AnnotatedCommit, AutotagOption, BranchType, Config, ConfigLevel, ErrorClass, ErrorCode, FetchOptions, IndexAddOption, ObjectType, PushOptions, Reference, Repository, StatusOptions, build::{CheckoutBuilder, RepoBuilder}};
 use walkdir::WalkDir;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -124,6 +131,12 @@ fn debug_repository(repo: &Repository, f: &mut fmt::Formatter) -> fmt::Result {
 pub struct Git {
     #[debug(with = "debug_repository")]
     repo: Repository,
+    passphrase_provider: PassphraseProvider,
+    credentials_provider: CredentialsProvider,
+    signing_keys: Vec<String>,
+    signature_policy: SignaturePolicy,
+    // messages of the changes made during a batch, which are committed at once
+    batch: Option<Vec<String>>,
 }
 
 #[derive(Debug)]
@@ -135,22 +148,112 @@ pub enum GitRemote {
 type GitResult<T> = Result<T, git2::Error>;
 
 impl Git {
-    pub(crate) fn open(path: &Path) -> GitResult<Option<Self>> {
+    pub(crate) fn open(
+        path: &Path,
+        passphrase_provider: PassphraseProvider,
+        credentials_provider: CredentialsProvider,
+        signing_keys: Vec<String>,
+    ) -> GitResult<Option<Self>> {
         if path.join(".git").is_dir() {
             let repo = Repository::open(path)?;
-            Ok(Some(Self { repo }))
+            Ok(Some(Self {
+                repo,
+                passphrase_provider,
+                credentials_provider,
+                signing_keys,
+                signature_policy: SignaturePolicy::AcceptAll,
+                batch: None,
+            }))
         } else {
             Ok(None)
         }
     }
 
+    pub(crate) fn init(
+        path: &Path,
+        passphrase_provider: PassphraseProvider,
+        credentials_provider: CredentialsProvider,
+        signing_keys: Vec<String>,
+    ) -> GitResult<Self> {
+        if path.join(".git").exists() {
+            return Err(git2::Error::new(
+                ErrorCode::Exists,
+                ErrorClass::Repository,
+                "Password store already uses git",
+            ));
+        }
+
+        let repo = Repository::init(path)?;
+        let mut config = repo.config()?.open_level(ConfigLevel::Local)?;
+        config.set_bool("diff.gpg.binary", true)?;
+        config.set_str(
+            "diff.gpg.textconv",
+            "gpg2 -d --quiet --yes --compress-algo=none --no-encrypt-to --batch --use-agent",
+        )?;
+
+        let mut me = Self {
+            repo,
+            passphrase_provider,
+            credentials_provider,
+            signing_keys,
+            signature_policy: SignaturePolicy::AcceptAll,
+            batch: None,
+        };
+        // the caller adds its own files before the initial commit
+        let mut index = me.repo.index()?;
+        index.add_all(&["*"], IndexAddOption::DEFAULT, None)?;
+        index.write()?;
+
+        Ok(me)
+    }
+
+    pub(crate) fn clone<U: AsRef<str>>(
+        url: U,
+        path: &Path,
+        passphrase_provider: PassphraseProvider,
+        credentials_provider: CredentialsProvider,
+        signing_keys: Vec<String>,
+    ) -> GitResult<Self> {
+        // there is no repository yet, so the credential helpers come from the global config
+        let mut fo = FetchOptions::new();
+        fo.remote_callbacks(credentials_provider.callbacks(Config::open_default()?));
+        let repo = RepoBuilder::new()
+            .fetch_options(fo)
+            .clone(url.as_ref(), path)?;
+        Ok(Self {
+            repo,
+            passphrase_provider,
+            credentials_provider,
+            signing_keys,
+            signature_policy: SignaturePolicy::AcceptAll,
+            batch: None,
+        })
+    }
+
+    pub fn set_credentials_provider<C: Into<CredentialsProvider>>(&mut self, provider: C) {
+        self.credentials_provider = provider.into();
+    }
+
+    pub fn add_remote<N: AsRef<str>, U: AsRef<str>>(&mut self, name: N, url: U) -> GitResult<()> {
+        self.repo.remote(name.as_ref(), url.as_ref())?;
+        Ok(())
+    }
+
     pub fn fetch(&self) -> GitResult<()> {
+        self.fetch_with_progress(|_| {})
+    }
+
+    pub fn fetch_with_progress<F: FnMut(GitProgress)>(&self, mut progress: F) -> GitResult<()> {
+        let reporter = ProgressReporter::new(&mut progress);
         for remote in self.repo.remotes()?.into_iter() {
             let mut remote = self
                 .repo
                 .find_remote(remote.expect("Remote name not valid utf-8 🤷"))?;
+            let mut callbacks = self.credentials_provider.callbacks(self.repo.config()?);
+            reporter.attach(&mut callbacks);
             let mut fo = FetchOptions::new();
             fo.download_tags(AutotagOption::All);
+            fo.remote_callbacks(callbacks);
             remote.fetch::<&str>(&[], Some(&mut fo), None)?;
         }
 
@@ -220,8 +323,10 @@ impl Git {
             .find_commit(self.repo.merge_base(local_commit.id(), remote_commit.id())?)?
             .tree()?;
         let idx = self.repo.merge_trees(&ancestor, &local_tree, &remote_tree, None)?;
+        let signing_keys = self.signing_keys.clone();
+        let passphrase_provider = self.passphrase_provider.clone();
 
-        ConflictResolver::from_index(idx, &self.repo, move |repo, idx| {
+        ConflictResolver::from_index(idx, &self.repo, &self.passphrase_provider, move |repo, idx| {
             let mut idx = idx.expect("Index not set");
             if idx.has_conflicts() {
                 return Err(git2::Error::new(git2::ErrorCode::Conflict, git2::ErrorClass::Merge, "Not all conflicts resolved"));
@@ -229,14 +334,13 @@ impl Git {
             let result_tree = repo.find_tree(idx.write_tree_to(repo)?)?;
             // now create the merge commit
             let msg = format!("Merge {} into {}", remote_commit_name.trim_start_matches("refs/remotes/"), local_commit_name.trim_start_matches("refs/heads/"));
-            let sig = repo.signature()?;
             let local_commit = repo.find_commit(local_commit.id())?;
             let remote_commit = repo.find_commit(remote_commit.id())?;
             // Do our merge commit and set current branch head to that commit.
-            let _merge_commit = repo.commit(
-                Some("HEAD"),
-                &sig,
-                &sig,
+            let _merge_commit = create_commit(
+                repo,
+                &signing_keys,
+                &passphrase_provider,
                 &msg,
                 &result_tree,
                 &[&local_commit, &remote_commit],
@@ -273,6 +377,8 @@ impl Git {
             .repo
             .reference_to_annotated_commit(&upstream_branch_ref)?;
 
+        self.check_signatures(current_branch.id(), upstream_branch.id())?;
+
         let analysis = self.repo.merge_analysis_for_ref(&current_branch_ref, &[&upstream_branch])?;
         if analysis.0.is_none() {
             return Err(git2::Error::new(
@@ -300,11 +406,26 @@ impl Git {
     }
 
     pub fn pull(&mut self) -> GitResult<ConflictResolver> {
-        self.fetch()?;
+        self.pull_with_progress(|_| {})
+    }
+
+    pub fn pull_with_progress<F: FnMut(GitProgress)>(
+        &mut self,
+        progress: F,
+    ) -> GitResult<ConflictResolver> {
+        self.fetch_with_progress(progress)?;
         self.merge()
     }
 
     pub fn push(&mut self, remote: GitRemote) -> GitResult<()> {
+        self.push_with_progress(remote, |_| {})
+    }
+
+    pub fn push_with_progress<F: FnMut(GitProgress)>(
+        &mut self,
+        remote: GitRemote,
+        mut progress: F,
+    ) -> GitResult<()> {
         let head = self.repo.head()?;
         let branch_name = head.name().expect("Branch name not valid utf-8 🤷");
         let remote = match remote {
@@ -318,9 +439,14 @@ impl Git {
         };
 
         let mut remote = self.repo.find_remote(&remote)?;
-        remote.push(&[branch_name], None)?;
-
-        Ok(())
+        let reporter = ProgressReporter::new(&mut progress);
+        let mut callbacks = self.credentials_provider.callbacks(self.repo.config()?);
+        reporter.attach(&mut callbacks);
+        let mut po = PushOptions::new();
+        po.remote_callbacks(callbacks);
+        remote.push(&[branch_name], Some(&mut po))?;
+
+        reporter.check_rejections()
     }
 
     pub fn status(&mut self) -> GitResult<GitStatuses> {
@@ -359,7 +485,11 @@ impl Git {
     }
 
     pub fn commit<M: Into<String>>(&mut self, message: M) -> GitResult<()> {
-        let me = self.repo.signature()?;
+        if let Some(messages) = &mut self.batch {
+            messages.push(message.into());
+            return Ok(());
+        }
+
         let tree_id = self.repo.index()?.write_tree()?;
         let tree = self.repo.find_tree(tree_id)?;
         // FIXME: closure hack until try-blocks are stable
@@ -376,8 +506,14 @@ impl Git {
         })();
         let parents = last_commit.iter().collect::<Vec<_>>();
 
-        self.repo
-            .commit(Some("HEAD"), &me, &me, &message.into(), &tree, &parents)?;
+        create_commit(
+            &self.repo,
+            &self.signing_keys,
+            &self.passphrase_provider,
+            &message.into(),
+            &tree,
+            &parents,
+        )?;
         Ok(())
     }
 
@@ -0,0 +1,168 @@
+use std::cell::RefCell;
+
+use git2::{ErrorClass, ErrorCode, Progress, RemoteCallbacks};
+
+use super::GitResult;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransferProgress {
+    total_objects: usize,
+    received_objects: usize,
+    indexed_objects: usize,
+    local_objects: usize,
+    total_deltas: usize,
+    indexed_deltas: usize,
+    received_bytes: usize,
+}
+
+impl TransferProgress {
+    fn new(progress: &Progress) -> Self {
+        Self {
+            total_objects: progress.total_objects(),
+            received_objects: progress.received_objects(),
+            indexed_objects: progress.indexed_objects(),
+            local_objects: progress.local_objects(),
+            total_deltas: progress.total_deltas(),
+            indexed_deltas: progress.indexed_deltas(),
+            received_bytes: progress.received_bytes(),
+        }
+    }
+
+    pub fn total_objects(&self) -> usize {
+        self.total_objects
+    }
+
+    pub fn received_objects(&self) -> usize {
+        self.received_objects
+    }
+
+    pub fn indexed_objects(&self) -> usize {
+        self.indexed_objects
+    }
+
+    // objects which were already available locally and did not need to be downloaded
+    pub fn local_objects(&self) -> usize {
+        self.local_objects
+    }
+
+    pub fn total_deltas(&self) -> usize {
+        self.total_deltas
+    }
+
+    pub fn indexed_deltas(&self) -> usize {
+        self.indexed_deltas
+    }
+
+    pub fn received_bytes(&self) -> usize {
+        self.received_bytes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PushStatus {
+    reference: String,
+    rejection: Option<String>,
+}
+
+impl PushStatus {
+    pub fn reference(&self) -> &str {
+        &self.reference
+    }
+
+    // the message of the remote if it refused to update the reference
+    pub fn rejection(&self) -> Option<&str> {
+        self.rejection.as_deref()
+    }
+
+    pub fn is_rejected(&self) -> bool {
+        self.rejection.is_some()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GitProgress {
+    /// Objects received and indexed while fetching
+    Transfer(TransferProgress),
+    /// Objects sent while pushing
+    PushTransfer {
+        current: usize,
+        total: usize,
+        bytes: usize,
+    },
+    /// Text the remote sends along, e.g. "Counting objects: 100% (3/3), done."
+    Remote(String),
+    /// The outcome of a pushed reference
+    PushStatus(PushStatus),
+}
+
+pub(crate) struct ProgressReporter<'a> {
+    progress: RefCell<&'a mut dyn FnMut(GitProgress)>,
+    rejected: RefCell<Vec<PushStatus>>,
+}
+
+impl<'a> ProgressReporter<'a> {
+    pub(crate) fn new(progress: &'a mut dyn FnMut(GitProgress)) -> Self {
+        Self {
+            progress: RefCell::new(progress),
+            rejected: RefCell::new(Vec::new()),
+        }
+    }
+
+    fn report(&self, progress: GitProgress) {
+        (&mut *self.progress.borrow_mut())(progress);
+    }
+
+    pub(crate) fn attach<'b>(&'b self, callbacks: &mut RemoteCallbacks<'b>) {
+        callbacks.transfer_progress(move |progress| {
+            self.report(GitProgress::Transfer(TransferProgress::new(&progress)));
+            true
+        });
+        callbacks.sideband_progress(move |text| {
+            self.report(GitProgress::Remote(String::from_utf8_lossy(text).into_owned()));
+            true
+        });
+        callbacks.push_transfer_progress(move |current, total, bytes| {
+            self.report(GitProgress::PushTransfer {
+                current,
+                total,
+                bytes,
+            });
+        });
+        callbacks.push_update_reference(move |reference, rejection| {
+            let status = PushStatus {
+                reference: reference.to_owned(),
+                rejection: rejection.map(str::to_owned),
+            };
+            if status.is_rejected() {
+                self.rejected.borrow_mut().push(status.clone());
+            }
+            self.report(GitProgress::PushStatus(status));
+            Ok(())
+        });
+    }
+
+    // libgit2 considers a push successful even if the remote refused some of the references
+    pub(crate) fn check_rejections(&self) -> GitResult<()> {
+        let rejected = self.rejected.borrow();
+        if rejected.is_empty() {
+            return Ok(());
+        }
+
+        let reasons = rejected
+            .iter()
+            .map(|status| {
+                format!(
+                    "{} ({})",
+                    status.reference,
+                    status.rejection.as_deref().unwrap_or_default()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        Err(git2::Error::new(
+            ErrorCode::NotFastForward,
+            ErrorClass::Reference,
+            format!("Remote rejected {}", reasons),
+        ))
+    }
+}
@@ -0,0 +1,221 @@
+use git2::{Commit, ErrorClass, ErrorCode, Oid, Repository, Tree};
+use gpgme::{Context, Protocol, Validity};
+
+use super::{Git, GitResult};
+use crate::PassphraseProvider;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SignaturePolicy {
+    /// Merge upstream commits regardless of their signatures
+    AcceptAll,
+    /// Refuse to merge upstream commits without a good signature
+    RejectUnsigned,
+    /// Refuse to merge upstream commits unless they are signed by a fully trusted key or one
+    /// of the store's signing keys
+    RejectUntrusted,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommitSignature {
+    Unsigned,
+    /// The signature does not match the commit or was made by an unknown key
+    Bad,
+    Good { fingerprint: String, trusted: bool },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifiedCommit {
+    id: Oid,
+    summary: String,
+    signature: CommitSignature,
+}
+
+impl VerifiedCommit {
+    pub fn id(&self) -> Oid {
+        self.id
+    }
+
+    pub fn summary(&self) -> &str {
+        &self.summary
+    }
+
+    pub fn signature(&self) -> &CommitSignature {
+        &self.signature
+    }
+
+    fn rejection(&self, policy: SignaturePolicy) -> Option<&'static str> {
+        match (policy, &self.signature) {
+            (SignaturePolicy::AcceptAll, _) => None,
+            (_, CommitSignature::Unsigned) => Some("is not signed"),
+            (_, CommitSignature::Bad) => Some("has a bad signature"),
+            (SignaturePolicy::RejectUntrusted, CommitSignature::Good { trusted: false, .. }) => {
+                Some("is signed by an untrusted key")
+            }
+            _ => None,
+        }
+    }
+}
+
+fn gpg_error(err: gpgme::Error) -> git2::Error {
+    git2::Error::from_str(&format!("GPG: {}", err))
+}
+
+fn sign(
+    content: &[u8],
+    signing_keys: &[String],
+    passphrase_provider: &PassphraseProvider,
+) -> GitResult<String> {
+    let mut ctx = passphrase_provider.context().map_err(gpg_error)?;
+    ctx.set_armor(true);
+    for id in signing_keys {
+        let key = ctx.get_secret_key(id).map_err(gpg_error)?;
+        ctx.add_signer(&key).map_err(gpg_error)?;
+    }
+
+    let mut signature = Vec::new();
+    passphrase_provider
+        .provide(&mut ctx, |ctx| ctx.sign_detached(content, &mut signature))
+        .map_err(gpg_error)?;
+    String::from_utf8(signature).map_err(|_| git2::Error::from_str("Signature is not armored"))
+}
+
+// like git and pass, commits are only signed if `commit.gpgsign` is set. the store's signing
+// keys take precedence over `user.signingkey`, without either gpg picks its default key
+pub(crate) fn create_commit(
+    repo: &Repository,
+    signing_keys: &[String],
+    passphrase_provider: &PassphraseProvider,
+    message: &str,
+    tree: &Tree,
+    parents: &[&Commit],
+) -> GitResult<Oid> {
+    let config = repo.config()?;
+    let me = repo.signature()?;
+    if !config.get_bool("commit.gpgsign").unwrap_or(false) {
+        return repo.commit(Some("HEAD"), &me, &me, message, tree, parents);
+    }
+
+    let signing_keys = if signing_keys.is_empty() {
+        config.get_string("user.signingkey").into_iter().collect()
+    } else {
+        signing_keys.to_vec()
+    };
+    let buffer = repo.commit_create_buffer(&me, &me, message, tree, parents)?;
+    let content = buffer
+        .as_str()
+        .ok_or_else(|| git2::Error::from_str("Commit is not valid utf-8"))?;
+    let signature = sign(content.as_bytes(), &signing_keys, passphrase_provider)?;
+    let id = repo.commit_signed(content, &signature, None)?;
+
+    // commit_signed does not move HEAD like commit does
+    let head = repo.find_reference("HEAD")?;
+    let reflog = format!("commit: {}", message.lines().next().unwrap_or_default());
+    match head.symbolic_target() {
+        Some(branch) => {
+            repo.reference(branch, id, true, &reflog)?;
+        }
+        None => repo.set_head_detached(id)?,
+    }
+
+    Ok(id)
+}
+
+impl Git {
+    // the store's signing keys are trusted even if the user never certified them in gpg
+    fn is_trusted(&self, ctx: &mut Context, validity: Validity, fingerprint: &str) -> bool {
+        matches!(validity, Validity::Full | Validity::Ultimate)
+            || self.signing_keys.iter().any(|id| {
+                ctx.get_key(id)
+                    .ok()
+                    .map_or(false, |key| key.fingerprint() == Ok(fingerprint))
+            })
+    }
+
+    pub fn set_signature_policy(&mut self, policy: SignaturePolicy) {
+        self.signature_policy = policy;
+    }
+
+    pub fn verify_commit(&self, id: Oid) -> GitResult<VerifiedCommit> {
+        let commit = self.repo.find_commit(id)?;
+        let summary = String::from_utf8_lossy(commit.summary_bytes().unwrap_or_default());
+        let (signature, content) = match self.repo.extract_signature(&id, None) {
+            Ok(signed) => signed,
+            Err(err) if err.code() == ErrorCode::NotFound => {
+                return Ok(VerifiedCommit {
+                    id,
+                    summary: summary.into_owned(),
+                    signature: CommitSignature::Unsigned,
+                });
+            }
+            Err(err) => return Err(err),
+        };
+
+        let mut ctx = Context::from_protocol(Protocol::OpenPgp).map_err(gpg_error)?;
+        let signature = match ctx.verify_detached(&*signature, &*content) {
+            Ok(result) => {
+                // compare primary key fingerprints, as the signature may stem from a subkey
+                let good = result.signatures().find(|signature| signature.status().is_ok());
+                let primary = good.as_ref().and_then(|signature| {
+                    let key = ctx.get_key(signature.fingerprint().ok()?).ok()?;
+                    key.fingerprint().ok().map(str::to_owned)
+                });
+                match (good, primary) {
+                    (Some(good), Some(fingerprint)) => CommitSignature::Good {
+                        trusted: self.is_trusted(&mut ctx, good.validity(), &fingerprint),
+                        fingerprint,
+                    },
+                    _ => CommitSignature::Bad,
+                }
+            }
+            Err(_) => CommitSignature::Bad,
+        };
+
+        Ok(VerifiedCommit {
+            id,
+            summary: summary.into_owned(),
+            signature,
+        })
+    }
+
+    // the commits a merge of `upstream` into `local` would bring in, newest first
+    pub(crate) fn verify_range(
+        &self,
+        local: Oid,
+        upstream: Oid,
+    ) -> GitResult<Vec<VerifiedCommit>> {
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.push(upstream)?;
+        revwalk.hide(local)?;
+
+        revwalk.map(|id| self.verify_commit(id?)).collect()
+    }
+
+    pub fn verify_incoming(&self) -> GitResult<Vec<VerifiedCommit>> {
+        let head = self.repo.head()?;
+        let branch = head.name().expect("Branch name not valid utf-8 🤷");
+        let upstream = self.repo.branch_upstream_name(branch)?;
+        let upstream = self
+            .repo
+            .find_reference(upstream.as_str().expect("Remote branch name not valid utf-8 🤷"))?;
+
+        self.verify_range(head.peel_to_commit()?.id(), upstream.peel_to_commit()?.id())
+    }
+
+    pub(crate) fn check_signatures(&self, local: Oid, upstream: Oid) -> GitResult<()> {
+        if self.signature_policy == SignaturePolicy::AcceptAll {
+            return Ok(());
+        }
+
+        for commit in self.verify_range(local, upstream)? {
+            if let Some(reason) = commit.rejection(self.signature_policy) {
+                return Err(git2::Error::new(
+                    ErrorCode::GenericError,
+                    ErrorClass::Merge,
+                    format!("Commit {} '{}' {}", commit.id, commit.summary, reason),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -6,6 +6,9 @@ use crate::{Directory, Password, Store, StoreError};
 #[cfg(feature = "passphrase-utils")]
 use crate::passphrase_utils::{AnalyzedPassphrase, PassphraseGenerator};
 
+#[cfg(feature = "otp")]
+use crate::parsed::Otp;
+
 pub struct PasswordInserter {
     pub(crate) parent: NodeId,
     pub(crate) path: PathBuf,
@@ -46,6 +49,25 @@ impl PasswordInserter {
         self
     }
 
+    #[cfg(feature = "otp")]
+    pub fn otp(&mut self, otp: &Otp) -> &mut Self {
+        self.lines.push(otp.to_uri());
+        self.changes.push("Add OTP secret to password".into());
+        self
+    }
+
+    #[cfg(feature = "otp")]
+    pub fn otp_uri(&mut self, uri: &str) -> Result<&mut Self, StoreError> {
+        let otp = Otp::from_uri(uri)?;
+        Ok(self.otp(&otp))
+    }
+
+    #[cfg(feature = "otp-qr")]
+    pub fn otp_qr_image(&mut self, image: &[u8]) -> Result<&mut Self, StoreError> {
+        let otp = Otp::from_qr_image(image)?;
+        Ok(self.otp(&otp))
+    }
+
     #[cfg(feature = "passphrase-utils")]
     pub fn generator(&mut self) -> PassphraseGenerator<&mut Self> {
         PassphraseGenerator::new(move |passphrase| {
@@ -1,14 +1,19 @@
 mod builder;
+mod clipboard;
 mod decrypted_password;
 mod directory;
 mod entry;
 mod error;
+mod fuzzy_matches;
 mod git;
 mod inserter;
 mod matched_entries;
 mod matched_passwords;
 mod pass_node;
 mod password;
+mod reencrypt;
+mod restore;
+mod search_index;
 mod store;
 mod traversal;
 pub(crate) mod util;
@@ -19,11 +24,16 @@ pub mod passphrase_utils;
 #[cfg(feature = "parsed-passwords")]
 pub mod parsed;
 
+#[cfg(feature = "qr-codes")]
+mod qr_code;
+
 pub use builder::*;
+pub use clipboard::*;
 pub use decrypted_password::*;
 pub use directory::*;
 pub use entry::*;
 pub use error::*;
+pub use fuzzy_matches::*;
 pub use git::*;
 pub use inserter::*;
 pub use matched_entries::*;
@@ -31,5 +41,11 @@ pub use matched_passwords::*;
 pub(crate) use pass_node::*;
 pub use pass_node::EntryKind;
 pub use password::*;
+pub(crate) use reencrypt::*;
+pub(crate) use restore::*;
+pub(crate) use search_index::*;
 pub use store::*;
 pub use traversal::*;
+
+#[cfg(feature = "qr-codes")]
+pub use qr_code::*;
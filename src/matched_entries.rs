@@ -1,28 +1,171 @@
-use crate::{Entries, Entry};
+use globset::{GlobBuilder, GlobMatcher};
+use regex::{Regex, RegexBuilder};
+use std::path::{Path, PathBuf};
 
-pub struct MatchedEntries<'a, 'b> {
-    pattern: &'b str,
+use crate::{Entries, Entry, EntryKind, StoreError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchMode {
+    Exact,
+    Substring,
+    Glob,
+    Regex,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchTarget {
+    Name,
+    Path,
+    // the absolute path including the .gpg extension, which is what `Store::find` matches
+    AbsolutePath,
+}
+
+#[derive(Debug, Clone)]
+pub struct FindOptions {
+    mode: MatchMode,
+    target: MatchTarget,
+    case_sensitive: bool,
+}
+
+impl Default for FindOptions {
+    fn default() -> Self {
+        Self {
+            mode: MatchMode::Substring,
+            target: MatchTarget::Path,
+            case_sensitive: false,
+        }
+    }
+}
+
+impl FindOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mode(&mut self, mode: MatchMode) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn target(&mut self, target: MatchTarget) -> &mut Self {
+        self.target = target;
+        self
+    }
+
+    pub fn case_sensitive(&mut self, case_sensitive: bool) -> &mut Self {
+        self.case_sensitive = case_sensitive;
+        self
+    }
+}
+
+pub(crate) enum Matcher {
+    Exact(String, bool),
+    Substring(String, bool),
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+fn invalid_pattern<E>(pattern: &str, err: E) -> StoreError
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    StoreError::InvalidPattern(pattern.to_owned(), Box::new(err))
+}
+
+fn fold_case(text: &str, case_sensitive: bool) -> String {
+    if case_sensitive {
+        text.to_owned()
+    } else {
+        text.to_lowercase()
+    }
+}
+
+impl Matcher {
+    pub(crate) fn new(
+        pattern: &str,
+        mode: MatchMode,
+        case_sensitive: bool,
+    ) -> Result<Self, StoreError> {
+        Ok(match mode {
+            MatchMode::Exact => Matcher::Exact(fold_case(pattern, case_sensitive), case_sensitive),
+            MatchMode::Substring => {
+                Matcher::Substring(fold_case(pattern, case_sensitive), case_sensitive)
+            }
+            MatchMode::Glob => Matcher::Glob(
+                GlobBuilder::new(pattern)
+                    .case_insensitive(!case_sensitive)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|err| invalid_pattern(pattern, err))?
+                    .compile_matcher(),
+            ),
+            MatchMode::Regex => Matcher::Regex(
+                RegexBuilder::new(pattern)
+                    .case_insensitive(!case_sensitive)
+                    .build()
+                    .map_err(|err| invalid_pattern(pattern, err))?,
+            ),
+        })
+    }
+
+    pub(crate) fn is_match(&self, candidate: &str) -> bool {
+        match self {
+            Matcher::Exact(pattern, case_sensitive) => {
+                fold_case(candidate, *case_sensitive) == *pattern
+            }
+            Matcher::Substring(pattern, case_sensitive) => {
+                fold_case(candidate, *case_sensitive).contains(pattern.as_str())
+            }
+            Matcher::Glob(glob) => glob.is_match(candidate),
+            Matcher::Regex(regex) => regex.is_match(candidate),
+        }
+    }
+}
+
+pub struct MatchedEntries<'a> {
+    matcher: Matcher,
+    target: MatchTarget,
+    root: PathBuf,
     traverser: Entries<'a>,
 }
 
-impl<'a, 'b> MatchedEntries<'a, 'b> {
-    pub(crate) fn new(pattern: &'b str, traverser: Entries<'a>) -> Self {
-        Self { pattern, traverser }
+impl<'a> MatchedEntries<'a> {
+    pub(crate) fn new(
+        pattern: &str,
+        options: &FindOptions,
+        root: &Path,
+        traverser: Entries<'a>,
+    ) -> Result<Self, StoreError> {
+        Ok(Self {
+            matcher: Matcher::new(pattern, options.mode, options.case_sensitive)?,
+            target: options.target,
+            root: root.to_owned(),
+            traverser,
+        })
+    }
+
+    // store-relative paths are written like pass shows them: without the .gpg extension
+    fn candidate(&self, entry: &Entry) -> String {
+        match self.target {
+            MatchTarget::Name => entry.name().to_owned(),
+            MatchTarget::Path => {
+                let path = entry.path().strip_prefix(&self.root).unwrap_or(entry.path());
+                match entry.kind() {
+                    EntryKind::Password => path.with_extension("").display().to_string(),
+                    _ => path.display().to_string(),
+                }
+            }
+            MatchTarget::AbsolutePath => entry.path().display().to_string(),
+        }
     }
 }
 
-impl<'a, 'b> Iterator for MatchedEntries<'a, 'b> {
+impl<'a> Iterator for MatchedEntries<'a> {
     type Item = Entry;
 
     fn next(&mut self) -> Option<Self::Item> {
         while let Some(next) = self.traverser.next() {
-            if next
-                .path()
-                .to_string_lossy()
-                .to_lowercase()
-                .find(&self.pattern.to_lowercase())
-                .is_some()
-            {
+            if self.matcher.is_match(&self.candidate(&next)) {
                 return Some(next);
             }
         }
@@ -1,4 +1,11 @@
-use crate::{Entries, Password};
+use regex::{Regex, RegexBuilder};
+use std::{
+    ops::Range,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use crate::{Entries, Password, Position, SearchIndex, StoreError, StoreErrors};
 
 pub struct MatchedPasswords<'a, 'b> {
     pattern: &'b str,
@@ -32,3 +39,261 @@ impl<'a, 'b> Iterator for MatchedPasswords<'a, 'b> {
         None
     }
 }
+
+/// Options for `Store::grep_with`.
+///
+/// The search index (see `IndexLocation`) never contains passphrases, so grep only searches
+/// it instead of decrypting every password if passphrases are skipped. The results are the
+/// same with and without an index.
+#[derive(Debug, Clone)]
+pub struct GrepOptions {
+    regex: bool,
+    case_sensitive: bool,
+    skip_passphrase: bool,
+    threads: Option<usize>,
+}
+
+impl Default for GrepOptions {
+    fn default() -> Self {
+        Self {
+            regex: false,
+            case_sensitive: false,
+            skip_passphrase: false,
+            threads: None,
+        }
+    }
+}
+
+impl GrepOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn regex(&mut self, regex: bool) -> &mut Self {
+        self.regex = regex;
+        self
+    }
+
+    pub fn case_sensitive(&mut self, case_sensitive: bool) -> &mut Self {
+        self.case_sensitive = case_sensitive;
+        self
+    }
+
+    // the first line of every password is not searched
+    pub fn skip_passphrase(&mut self, skip_passphrase: bool) -> &mut Self {
+        self.skip_passphrase = skip_passphrase;
+        self
+    }
+
+    pub fn threads(&mut self, threads: usize) -> &mut Self {
+        self.threads = Some(threads.max(1));
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchedLine {
+    position: Position,
+    line: String,
+    ranges: Vec<Range<usize>>,
+}
+
+impl MatchedLine {
+    // position 0 is the passphrase, like in DecryptedPassword::lines
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+
+    // byte ranges of all matches in the line
+    pub fn ranges(&self) -> &[Range<usize>] {
+        &self.ranges
+    }
+}
+
+#[derive(Debug)]
+pub struct GrepMatch {
+    password: Password,
+    lines: Vec<MatchedLine>,
+}
+
+impl GrepMatch {
+    pub fn password(&self) -> &Password {
+        &self.password
+    }
+
+    pub fn into_password(self) -> Password {
+        self.password
+    }
+
+    pub fn lines(&self) -> &[MatchedLine] {
+        &self.lines
+    }
+}
+
+#[derive(Debug)]
+pub struct GrepResults {
+    matches: Vec<GrepMatch>,
+    errors: Vec<StoreError>,
+}
+
+impl GrepResults {
+    pub fn matches(&self) -> &[GrepMatch] {
+        &self.matches
+    }
+
+    pub fn into_matches(self) -> Vec<GrepMatch> {
+        self.matches
+    }
+
+    pub fn errors(&self) -> StoreErrors {
+        StoreErrors::new(&self.errors)
+    }
+}
+
+fn match_lines<'a>(
+    lines: impl Iterator<Item = (Position, &'a str)>,
+    regex: &Regex,
+) -> Vec<MatchedLine> {
+    lines
+        .filter_map(|(position, line)| {
+            let ranges = regex
+                .find_iter(line)
+                .map(|found| found.range())
+                .collect::<Vec<_>>();
+            if ranges.is_empty() {
+                None
+            } else {
+                Some(MatchedLine {
+                    position,
+                    line: line.to_owned(),
+                    ranges,
+                })
+            }
+        })
+        .collect()
+}
+
+fn grep_password(
+    password: &Password,
+    regex: &Regex,
+    skip_passphrase: bool,
+) -> Result<Option<Vec<MatchedLine>>, StoreError> {
+    let decrypted = password.decrypt()?;
+    let lines = match_lines(
+        decrypted.lines().enumerate().skip(usize::from(skip_passphrase)),
+        regex,
+    );
+
+    Ok(Some(lines).filter(|lines| !lines.is_empty()))
+}
+
+// decrypts and searches all passwords on a pool of worker threads, results keep the traversal order
+pub(crate) fn search_parallel<T, F>(
+    passwords: Vec<Password>,
+    threads: Option<usize>,
+    search: F,
+) -> (Vec<(Password, T)>, Vec<StoreError>)
+where
+    T: Send + 'static,
+    F: Fn(&Password) -> Result<Option<T>, StoreError> + Send + Sync + 'static,
+{
+    let passwords = passwords.into_iter().enumerate().collect::<Vec<_>>();
+    let threads = threads
+        .or_else(|| thread::available_parallelism().ok().map(|threads| threads.get()))
+        .unwrap_or(1)
+        .min(passwords.len().max(1));
+
+    // every gpg operation gets its own context, so workers only share the queue
+    let queue = Arc::new(Mutex::new(passwords.into_iter()));
+    let search = Arc::new(search);
+    let (sender, receiver) = mpsc::channel();
+    let workers = (0..threads)
+        .map(|_| {
+            let queue = queue.clone();
+            let search = search.clone();
+            let sender = sender.clone();
+            thread::spawn(move || loop {
+                let next = queue.lock().expect("Search queue poisoned").next();
+                let (index, password) = match next {
+                    Some(next) => next,
+                    None => break,
+                };
+                let result = search(&password);
+                if sender.send((index, password, result)).is_err() {
+                    break;
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    drop(sender);
+
+    let mut results = receiver.iter().collect::<Vec<_>>();
+    for worker in workers {
+        worker.join().expect("Search worker panicked");
+    }
+    results.sort_by_key(|(index, ..)| *index);
+
+    let mut matches = Vec::new();
+    let mut errors = Vec::new();
+    for (_, password, result) in results {
+        match result {
+            Ok(Some(found)) => matches.push((password, found)),
+            Ok(None) => {}
+            Err(err) => errors.push(StoreError::Grep(
+                password.path().display().to_string(),
+                Box::new(err),
+            )),
+        }
+    }
+
+    (matches, errors)
+}
+
+pub(crate) fn grep_parallel(
+    pattern: &str,
+    options: &GrepOptions,
+    entries: Entries,
+    index: Option<&Mutex<SearchIndex>>,
+) -> Result<GrepResults, StoreError> {
+    let pattern_source = if options.regex {
+        pattern.to_owned()
+    } else {
+        regex::escape(pattern)
+    };
+    let regex = RegexBuilder::new(&pattern_source)
+        .case_insensitive(!options.case_sensitive)
+        .build()
+        .map_err(|err| StoreError::InvalidPattern(pattern.to_owned(), Box::new(err)))?;
+    let passwords = entries
+        .filter_map(|entry| entry.password())
+        .collect::<Vec<_>>();
+
+    // the index never contains passphrases, so it can only answer searches which skip them
+    if let Some(index) = index.filter(|_| options.skip_passphrase) {
+        let mut index = index.lock().expect("Search index poisoned");
+        let (passwords, errors) = index.refresh(passwords, options.threads);
+        let matches = passwords
+            .into_iter()
+            .filter_map(|password| {
+                let lines = match_lines(index.lines(&password)?, &regex);
+                Some(GrepMatch { password, lines }).filter(|found| !found.lines.is_empty())
+            })
+            .collect();
+        return Ok(GrepResults { matches, errors });
+    }
+
+    let skip_passphrase = options.skip_passphrase;
+    let (matches, errors) = search_parallel(passwords, options.threads, move |password| {
+        grep_password(password, &regex, skip_passphrase)
+    });
+    let matches = matches
+        .into_iter()
+        .map(|(password, lines)| GrepMatch { password, lines })
+        .collect();
+
+    Ok(GrepResults { matches, errors })
+}
@@ -5,11 +5,22 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::{pw_name, save_password_to_file, Position, Store, StoreError};
+use crate::{
+    pw_name, save_password_to_file, Clipboard, ClipboardGuard, Position, Store, StoreError,
+};
 
 #[cfg(feature = "passphrase-utils")]
 use crate::passphrase_utils::{AnalyzedPassphrase, PassphraseGenerator};
 
+#[cfg(feature = "qr-codes")]
+use crate::QrCode;
+
+#[cfg(feature = "otp")]
+use std::time::SystemTime;
+
+#[cfg(feature = "otp")]
+use crate::parsed::{Otp, OtpCode, OtpKind};
+
 #[derive(Parser, Debug)]
 #[grammar = "parsed/pass.pest"]
 struct PasswordParser;
@@ -18,6 +29,39 @@ struct PasswordParser;
 pub enum PasswordLine {
     Comment(String),
     Entry(String, String),
+    #[cfg(feature = "otp")]
+    Otp(String),
+}
+
+impl PasswordLine {
+    fn is_comment(&self) -> bool {
+        matches!(self, PasswordLine::Comment(..))
+    }
+
+    fn is_entry(&self) -> bool {
+        matches!(self, PasswordLine::Entry(..))
+    }
+
+    #[cfg(feature = "otp")]
+    fn from_otp_uri(uri: &str) -> Self {
+        PasswordLine::Otp(uri.to_owned())
+    }
+
+    // without the otp feature an otpauth uri stays the entry it always was
+    #[cfg(not(feature = "otp"))]
+    fn from_otp_uri(uri: &str) -> Self {
+        PasswordLine::Entry("otpauth".into(), uri["otpauth:".len()..].to_owned())
+    }
+
+    // describes the line in commit messages
+    fn kind_name(&self) -> String {
+        match self {
+            PasswordLine::Comment(_) => "comment".into(),
+            PasswordLine::Entry(key, _) => format!("{} entry", key),
+            #[cfg(feature = "otp")]
+            PasswordLine::Otp(_) => "OTP secret".into(),
+        }
+    }
 }
 
 impl fmt::Display for PasswordLine {
@@ -25,6 +69,8 @@ impl fmt::Display for PasswordLine {
         match self {
             PasswordLine::Comment(comment) => write!(f, "{}", comment),
             PasswordLine::Entry(key, value) => write!(f, "{}: {}", key, value),
+            #[cfg(feature = "otp")]
+            PasswordLine::Otp(uri) => write!(f, "{}", uri),
         }
     }
 }
@@ -87,6 +133,9 @@ impl DecryptedPassword {
                 Rule::comment => {
                     lines.push(PasswordLine::Comment(record.as_str().to_owned()));
                 }
+                Rule::otp => {
+                    lines.push(PasswordLine::from_otp_uri(record.as_str()));
+                }
                 _ => unreachable!(),
             }
         }
@@ -204,10 +253,7 @@ impl DecryptedPassword {
         position: Position,
         line: PasswordLine,
     ) -> Result<(), StoreError> {
-        let message = match &line {
-            PasswordLine::Comment(_) => "Add comment to password".into(),
-            PasswordLine::Entry(key, _) => format!("Add {} entry to password", key),
-        };
+        let message = format!("Add {} to password", line.kind_name());
         self.changes.push(message);
 
         let old_lines = self.lines.clone();
@@ -228,10 +274,7 @@ impl DecryptedPassword {
         position: Position,
         line: PasswordLine,
     ) -> Result<(), StoreError> {
-        let message = match &line {
-            PasswordLine::Comment(_) => "Replace comment in password".into(),
-            PasswordLine::Entry(key, _) => format!("Replace {} entry in password", key),
-        };
+        let message = format!("Replace {} in password", line.kind_name());
         self.changes.push(message);
 
         let old_lines = self.lines.clone();
@@ -254,10 +297,7 @@ impl DecryptedPassword {
         let old_lines = self.lines.clone();
         let old_line = self.lines.remove(position);
 
-        let message = match &old_line {
-            PasswordLine::Comment(_) => "Remove comment from password".into(),
-            PasswordLine::Entry(key, _) => format!("Remove {} entry from password", key),
-        };
+        let message = format!("Remove {} from password", old_line.kind_name());
         self.changes.push(message);
 
         match self.save(None, store) {
@@ -270,10 +310,7 @@ impl DecryptedPassword {
     }
 
     pub fn append_line(&mut self, store: &mut Store, line: PasswordLine) -> Result<(), StoreError> {
-        let message = match &line {
-            PasswordLine::Comment(_) => "Add comment to password".into(),
-            PasswordLine::Entry(key, _) => format!("Add {} entry to password", key),
-        };
+        let message = format!("Add {} to password", line.kind_name());
         self.changes.push(message);
 
         let old_lines = self.lines.clone();
@@ -293,10 +330,7 @@ impl DecryptedPassword {
         store: &mut Store,
         line: PasswordLine,
     ) -> Result<(), StoreError> {
-        let message = match &line {
-            PasswordLine::Comment(_) => "Add comment to password".into(),
-            PasswordLine::Entry(key, _) => format!("Add {} entry to password", key),
-        };
+        let message = format!("Add {} to password", line.kind_name());
         self.changes.push(message);
 
         let old_lines = self.lines.clone();
@@ -333,7 +367,7 @@ impl DecryptedPassword {
             .enumerate()
             .filter_map(|(position, line)| match line {
                 PasswordLine::Comment(comment) => Some((position, comment.as_str())),
-                PasswordLine::Entry(..) => None,
+                _ => None,
             })
     }
 
@@ -352,7 +386,7 @@ impl DecryptedPassword {
         position: Position,
         comment: C,
     ) -> Result<(), StoreError> {
-        if let Some(PasswordLine::Entry(..)) = self.lines.get(position) {
+        if self.lines.get(position).map_or(false, |line| !line.is_comment()) {
             return Err(StoreError::PasswordLineNotAComment(position));
         }
         self.replace_line(store, position, PasswordLine::Comment(comment.into()))
@@ -363,7 +397,7 @@ impl DecryptedPassword {
         store: &mut Store,
         position: Position,
     ) -> Result<PasswordLine, StoreError> {
-        if let Some(PasswordLine::Entry(..)) = self.lines.get(position) {
+        if self.lines.get(position).map_or(false, |line| !line.is_comment()) {
             return Err(StoreError::PasswordLineNotAComment(position));
         }
         self.remove_line(store, position)
@@ -391,7 +425,7 @@ impl DecryptedPassword {
             .enumerate()
             .filter_map(|(position, line)| match line {
                 PasswordLine::Entry(key, value) => Some((position, (key.as_str(), value.as_str()))),
-                PasswordLine::Comment(..) => None,
+                _ => None,
             })
     }
 
@@ -406,6 +440,157 @@ impl DecryptedPassword {
             .next()
     }
 
+    pub fn copy_passphrase(&self, clipboard: &Clipboard) -> Result<ClipboardGuard, StoreError> {
+        let passphrase = self
+            .passphrase()
+            .ok_or_else(|| StoreError::NothingToCopy("passphrase".to_owned()))?;
+        clipboard.copy(passphrase)
+    }
+
+    pub fn copy_comment(
+        &self,
+        clipboard: &Clipboard,
+        position: Position,
+    ) -> Result<ClipboardGuard, StoreError> {
+        match self.lines.get(position) {
+            Some(PasswordLine::Comment(comment)) => clipboard.copy(comment),
+            Some(_) => Err(StoreError::PasswordLineNotAComment(position)),
+            None => Err(StoreError::NothingToCopy(format!("comment {}", position))),
+        }
+    }
+
+    pub fn copy_entry(&self, clipboard: &Clipboard, key: &str) -> Result<ClipboardGuard, StoreError> {
+        let (_, value) = self
+            .entry(key)
+            .ok_or_else(|| StoreError::NothingToCopy(format!("entry '{}'", key)))?;
+        clipboard.copy(value)
+    }
+
+    #[cfg(feature = "qr-codes")]
+    pub fn qr_code(&self) -> Result<QrCode, StoreError> {
+        QrCode::new(self.to_string())
+    }
+
+    #[cfg(feature = "qr-codes")]
+    pub fn passphrase_qr_code(&self) -> Result<QrCode, StoreError> {
+        let passphrase = self
+            .passphrase()
+            .ok_or_else(|| StoreError::NothingToEncode("passphrase".to_owned()))?;
+        QrCode::new(passphrase)
+    }
+
+    #[cfg(feature = "qr-codes")]
+    pub fn comment_qr_code(&self, position: Position) -> Result<QrCode, StoreError> {
+        match self.lines.get(position) {
+            Some(PasswordLine::Comment(comment)) => QrCode::new(comment),
+            Some(_) => Err(StoreError::PasswordLineNotAComment(position)),
+            None => Err(StoreError::NothingToEncode(format!("comment {}", position))),
+        }
+    }
+
+    #[cfg(feature = "qr-codes")]
+    pub fn entry_qr_code(&self, key: &str) -> Result<QrCode, StoreError> {
+        let (_, value) = self
+            .entry(key)
+            .ok_or_else(|| StoreError::NothingToEncode(format!("entry '{}'", key)))?;
+        QrCode::new(value)
+    }
+
+    // pass-otp stores the URI either as the passphrase or on one of the following lines
+    #[cfg(feature = "otp")]
+    fn otp_position(&self) -> Option<Option<Position>> {
+        if let Some(passphrase) = &self.passphrase {
+            if passphrase.starts_with("otpauth://") {
+                return Some(None);
+            }
+        }
+        self.lines
+            .iter()
+            .position(|line| matches!(line, PasswordLine::Otp(..)))
+            .map(Some)
+    }
+
+    #[cfg(feature = "otp")]
+    pub fn otp(&self) -> Result<Otp, StoreError> {
+        match self.otp_position() {
+            Some(None) => Otp::from_uri(self.passphrase.as_deref().unwrap_or_default()),
+            Some(Some(position)) => Otp::from_uri(&self.lines[position].to_string()),
+            None => Err(StoreError::NoOtp(self.path.display().to_string())),
+        }
+    }
+
+    #[cfg(feature = "otp")]
+    pub fn totp_code(&self) -> Result<OtpCode, StoreError> {
+        self.totp_code_at(SystemTime::now())
+    }
+
+    #[cfg(feature = "otp")]
+    pub fn totp_code_at(&self, time: SystemTime) -> Result<OtpCode, StoreError> {
+        let otp = self.otp()?;
+        match otp.kind() {
+            OtpKind::Totp { .. } => Ok(otp.code_at(time)),
+            OtpKind::Hotp { .. } => Err(StoreError::NotTotp(self.path.display().to_string())),
+        }
+    }
+
+    // like pass-otp, the incremented counter is persisted before the code is handed out
+    #[cfg(feature = "otp")]
+    pub fn hotp_code(&mut self, store: &mut Store) -> Result<OtpCode, StoreError> {
+        let next = self
+            .otp()?
+            .increment()
+            .ok_or_else(|| StoreError::NotHotp(self.path.display().to_string()))?;
+
+        let position = self.otp_position().expect("OTP URI vanished from password");
+        let old_uri = match position {
+            None => self.passphrase.replace(next.to_uri()),
+            Some(position) => {
+                let old_line = std::mem::replace(
+                    &mut self.lines[position],
+                    PasswordLine::Otp(next.to_uri()),
+                );
+                Some(old_line.to_string())
+            }
+        };
+        let summary = format!(
+            "Increment HOTP counter for '{}' using libpass.",
+            pw_name(&self.path, store)
+        );
+        match self.save(Some(summary), store) {
+            Ok(()) => Ok(next.code_at(SystemTime::now())),
+            Err(err) => {
+                match position {
+                    None => self.passphrase = old_uri,
+                    Some(position) => {
+                        self.lines[position] = PasswordLine::Otp(old_uri.unwrap_or_default())
+                    }
+                }
+                Err(err)
+            }
+        }
+    }
+
+    #[cfg(feature = "otp")]
+    pub fn set_otp(&mut self, store: &mut Store, otp: &Otp) -> Result<(), StoreError> {
+        match self.otp_position() {
+            Some(None) => {
+                self.changes.push("Replace OTP secret in password".into());
+                let old_passphrase = self.passphrase.replace(otp.to_uri());
+                match self.save(None, store) {
+                    Ok(()) => Ok(()),
+                    Err(err) => {
+                        self.passphrase = old_passphrase;
+                        Err(err)
+                    }
+                }
+            }
+            Some(Some(position)) => {
+                self.replace_line(store, position, PasswordLine::Otp(otp.to_uri()))
+            }
+            None => self.append_line(store, PasswordLine::Otp(otp.to_uri())),
+        }
+    }
+
     pub fn insert_entry<K: Into<String>, V: Into<String>>(
         &mut self,
         store: &mut Store,
@@ -427,7 +612,7 @@ impl DecryptedPassword {
         key: K,
         value: V,
     ) -> Result<(), StoreError> {
-        if let Some(PasswordLine::Comment(..)) = self.lines.get(position) {
+        if self.lines.get(position).map_or(false, |line| !line.is_entry()) {
             return Err(StoreError::PasswordLineNotAnEntry(position));
         }
         self.replace_line(
@@ -442,7 +627,7 @@ impl DecryptedPassword {
         store: &mut Store,
         position: Position,
     ) -> Result<PasswordLine, StoreError> {
-        if let Some(PasswordLine::Comment(..)) = self.lines.get(position) {
+        if self.lines.get(position).map_or(false, |line| !line.is_entry()) {
             return Err(StoreError::PasswordLineNotAnEntry(position));
         }
         self.remove_line(store, position)
@@ -506,10 +691,7 @@ impl<'a> DecryptedPasswordBatchEdit<'a> {
     }
 
     pub fn insert_line(mut self, position: Position, line: PasswordLine) -> Self {
-        let message = match &line {
-            PasswordLine::Comment(_) => "Add comment to password".into(),
-            PasswordLine::Entry(key, _) => format!("Add {} entry to password", key),
-        };
+        let message = format!("Add {} to password", line.kind_name());
         self.changes.push(message);
 
         self.lines.insert(position, line);
@@ -517,10 +699,7 @@ impl<'a> DecryptedPasswordBatchEdit<'a> {
     }
 
     pub fn replace_line(mut self, position: Position, line: PasswordLine) -> Self {
-        let message = match &line {
-            PasswordLine::Comment(_) => "Replace comment in password".into(),
-            PasswordLine::Entry(key, _) => format!("Replace {} entry in password", key),
-        };
+        let message = format!("Replace {} in password", line.kind_name());
         self.changes.push(message);
 
         if let Some(old_line) = self.lines.get_mut(position) {
@@ -535,20 +714,14 @@ impl<'a> DecryptedPasswordBatchEdit<'a> {
     pub fn remove_line(mut self, position: Position) -> Self {
         let old_line = self.lines.remove(position);
 
-        let message = match &old_line {
-            PasswordLine::Comment(_) => "Remove comment from password".into(),
-            PasswordLine::Entry(key, _) => format!("Remove {} entry from password", key),
-        };
+        let message = format!("Remove {} from password", old_line.kind_name());
         self.changes.push(message);
 
         self
     }
 
     pub fn append_line(mut self, line: PasswordLine) -> Self {
-        let message = match &line {
-            PasswordLine::Comment(_) => "Add comment to password".into(),
-            PasswordLine::Entry(key, _) => format!("Add {} entry to password", key),
-        };
+        let message = format!("Add {} to password", line.kind_name());
         self.changes.push(message);
 
         self.lines.push(line);
@@ -556,10 +729,7 @@ impl<'a> DecryptedPasswordBatchEdit<'a> {
     }
 
     pub fn prepend_line(mut self, line: PasswordLine) -> Self {
-        let message = match &line {
-            PasswordLine::Comment(_) => "Add comment to password".into(),
-            PasswordLine::Entry(key, _) => format!("Add {} entry to password", key),
-        };
+        let message = format!("Add {} to password", line.kind_name());
         self.changes.push(message);
 
         self.lines.insert(0, line);
@@ -571,14 +741,14 @@ impl<'a> DecryptedPasswordBatchEdit<'a> {
     }
 
     pub fn replace_comment<C: Into<String>>(self, position: Position, comment: C) -> Self {
-        if let Some(PasswordLine::Entry(..)) = self.lines.get(position) {
+        if self.lines.get(position).map_or(false, |line| !line.is_comment()) {
             panic!("Line at position {0} is not a comment!", position);
         }
         self.replace_line(position, PasswordLine::Comment(comment.into()))
     }
 
     pub fn remove_comment(self, position: Position) -> Self {
-        if let Some(PasswordLine::Entry(..)) = self.lines.get(position) {
+        if self.lines.get(position).map_or(false, |line| !line.is_comment()) {
             panic!("Line at position {0} is not a comment!", position);
         }
         self.remove_line(position)
@@ -607,14 +777,14 @@ impl<'a> DecryptedPasswordBatchEdit<'a> {
         key: K,
         value: V,
     ) -> Self {
-        if let Some(PasswordLine::Comment(..)) = self.lines.get(position) {
+        if self.lines.get(position).map_or(false, |line| !line.is_entry()) {
             panic!("Line at position {0} is not an entry!", position);
         }
         self.replace_line(position, PasswordLine::Entry(key.into(), value.into()))
     }
 
     pub fn remove_entry(self, position: Position) -> Self {
-        if let Some(PasswordLine::Comment(..)) = self.lines.get(position) {
+        if self.lines.get(position).map_or(false, |line| !line.is_entry()) {
             panic!("Line at position {0} is not an entry!", position);
         }
         self.remove_line(position)
@@ -0,0 +1,129 @@
+use std::sync::Mutex;
+
+use crate::{
+    search_parallel, Entries, MatchMode, Matcher, Password, Position, SearchIndex, StoreError,
+    StoreErrors,
+};
+
+#[derive(Debug, Clone)]
+pub struct FieldQuery {
+    key: String,
+    pattern: String,
+    mode: MatchMode,
+    case_sensitive: bool,
+    threads: Option<usize>,
+}
+
+impl FieldQuery {
+    pub fn new<K: Into<String>, P: Into<String>>(key: K, pattern: P) -> Self {
+        Self {
+            key: key.into(),
+            pattern: pattern.into(),
+            mode: MatchMode::Exact,
+            case_sensitive: false,
+            threads: None,
+        }
+    }
+
+    pub fn mode(&mut self, mode: MatchMode) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    // applies to both the key and the value
+    pub fn case_sensitive(&mut self, case_sensitive: bool) -> &mut Self {
+        self.case_sensitive = case_sensitive;
+        self
+    }
+
+    pub fn threads(&mut self, threads: usize) -> &mut Self {
+        self.threads = Some(threads.max(1));
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct FieldMatch {
+    password: Password,
+    entries: Vec<(Position, String, String)>,
+}
+
+impl FieldMatch {
+    pub fn password(&self) -> &Password {
+        &self.password
+    }
+
+    pub fn into_password(self) -> Password {
+        self.password
+    }
+
+    pub fn entries(&self) -> &[(Position, String, String)] {
+        &self.entries
+    }
+}
+
+#[derive(Debug)]
+pub struct FieldResults {
+    matches: Vec<FieldMatch>,
+    errors: Vec<StoreError>,
+}
+
+impl FieldResults {
+    pub fn matches(&self) -> &[FieldMatch] {
+        &self.matches
+    }
+
+    pub fn into_matches(self) -> Vec<FieldMatch> {
+        self.matches
+    }
+
+    pub fn errors(&self) -> StoreErrors {
+        StoreErrors::new(&self.errors)
+    }
+}
+
+pub(crate) fn search_fields(
+    query: &FieldQuery,
+    entries: Entries,
+    index: Option<&Mutex<SearchIndex>>,
+) -> Result<FieldResults, StoreError> {
+    let key = Matcher::new(&query.key, MatchMode::Exact, query.case_sensitive)?;
+    let value = Matcher::new(&query.pattern, query.mode, query.case_sensitive)?;
+    let mut passwords = entries
+        .filter_map(|entry| entry.password())
+        .collect::<Vec<_>>();
+
+    // the index narrows the search down to passwords with a matching key, values are
+    // still read from the decrypted passwords to report their exact positions
+    let mut errors = Vec::new();
+    if let Some(index) = index {
+        let mut index = index.lock().expect("Search index poisoned");
+        let (refreshed, refresh_errors) = index.refresh(passwords, query.threads);
+        passwords = refreshed
+            .into_iter()
+            .filter(|password| {
+                index
+                    .keys(password)
+                    .map_or(false, |keys| keys.iter().any(|k| key.is_match(k)))
+            })
+            .collect();
+        errors = refresh_errors;
+    }
+
+    let (matches, search_errors) = search_parallel(passwords, query.threads, move |password| {
+        let decrypted = password.decrypt()?.parsed()?;
+        let entries = decrypted
+            .all_entries()
+            .filter(|(_, (k, v))| key.is_match(k) && value.is_match(v))
+            .map(|(position, (k, v))| (position, k.to_owned(), v.to_owned()))
+            .collect::<Vec<_>>();
+        Ok(Some(entries).filter(|entries| !entries.is_empty()))
+    });
+    let matches = matches
+        .into_iter()
+        .map(|(password, entries)| FieldMatch { password, entries })
+        .collect();
+    errors.extend(search_errors);
+
+    Ok(FieldResults { matches, errors })
+}
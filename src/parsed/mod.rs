@@ -1,8 +1,14 @@
 mod decrypted_password;
 mod conflicted_password;
+mod field_search;
 mod inserter;
+#[cfg(feature = "otp")]
+mod otp;
 
 pub(crate) use decrypted_password::Rule;
 pub use decrypted_password::*;
 pub use conflicted_password::*;
+pub use field_search::*;
 pub use inserter::*;
+#[cfg(feature = "otp")]
+pub use otp::*;
@@ -0,0 +1,392 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use url::Url;
+
+use crate::StoreError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtpAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl OtpAlgorithm {
+    fn name(&self) -> &'static str {
+        match self {
+            OtpAlgorithm::Sha1 => "SHA1",
+            OtpAlgorithm::Sha256 => "SHA256",
+            OtpAlgorithm::Sha512 => "SHA512",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtpKind {
+    Totp { period: u64 },
+    Hotp { counter: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OtpCode {
+    code: String,
+    remaining: Option<Duration>,
+}
+
+impl OtpCode {
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    // HOTP codes do not expire
+    pub fn remaining(&self) -> Option<Duration> {
+        self.remaining
+    }
+
+    pub fn seconds_remaining(&self) -> Option<u64> {
+        self.remaining.map(|remaining| remaining.as_secs())
+    }
+}
+
+impl fmt::Display for OtpCode {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.code)
+    }
+}
+
+#[derive(Clone)]
+pub struct Otp {
+    url: Url,
+    kind: OtpKind,
+    algorithm: OtpAlgorithm,
+    digits: u32,
+    secret: Vec<u8>,
+    label: String,
+    issuer: Option<String>,
+}
+
+impl Debug for Otp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Otp")
+            .field("kind", &self.kind)
+            .field("algorithm", &self.algorithm)
+            .field("digits", &self.digits)
+            .field("label", &self.label)
+            .field("issuer", &self.issuer)
+            .finish()
+    }
+}
+
+fn invalid(reason: &str) -> StoreError {
+    StoreError::InvalidOtpUri(reason.to_owned())
+}
+
+// authenticator apps are lenient about padding, case and grouping of the secret
+fn normalize_secret(secret: &str) -> String {
+    secret
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '=' && *c != '-')
+        .collect::<String>()
+        .to_uppercase()
+}
+
+impl Otp {
+    pub fn from_uri(uri: &str) -> Result<Self, StoreError> {
+        let url = Url::parse(uri.trim()).map_err(|_| invalid("not a valid URI"))?;
+        if url.scheme() != "otpauth" {
+            return Err(invalid("scheme is not otpauth"));
+        }
+
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+        let number = |name: &str| {
+            param(name)
+                .map(|value| {
+                    value
+                        .parse::<u64>()
+                        .map_err(|_| StoreError::InvalidOtpUri(format!("{} is not a number", name)))
+                })
+                .transpose()
+        };
+
+        let kind = match url.host_str() {
+            Some("totp") => OtpKind::Totp {
+                period: number("period")?.unwrap_or(30),
+            },
+            Some("hotp") => OtpKind::Hotp {
+                counter: number("counter")?.ok_or_else(|| invalid("HOTP URI has no counter"))?,
+            },
+            _ => return Err(invalid("type is neither totp nor hotp")),
+        };
+        if let OtpKind::Totp { period: 0 } = kind {
+            return Err(invalid("period must not be zero"));
+        }
+
+        let algorithm = match param("algorithm").map(|a| a.to_uppercase()).as_deref() {
+            None | Some("SHA1") => OtpAlgorithm::Sha1,
+            Some("SHA256") => OtpAlgorithm::Sha256,
+            Some("SHA512") => OtpAlgorithm::Sha512,
+            Some(_) => return Err(invalid("unsupported algorithm")),
+        };
+
+        let digits = number("digits")?.unwrap_or(6);
+        if !(6..=8).contains(&digits) {
+            return Err(invalid("digits must be between 6 and 8"));
+        }
+
+        let secret = normalize_secret(&param("secret").ok_or_else(|| invalid("no secret"))?);
+        let secret = BASE32_NOPAD
+            .decode(secret.as_bytes())
+            .map_err(|_| invalid("secret is not base32 encoded"))?;
+        if secret.is_empty() {
+            return Err(invalid("no secret"));
+        }
+
+        let label = percent_decode_str(url.path().trim_start_matches('/'))
+            .decode_utf8_lossy()
+            .into_owned();
+        let issuer = param("issuer").or_else(|| {
+            label
+                .split_once(':')
+                .map(|(issuer, _)| issuer.trim().to_owned())
+        });
+
+        Ok(Self {
+            url,
+            kind,
+            algorithm,
+            digits: digits as u32,
+            secret,
+            label,
+            issuer,
+        })
+    }
+
+    #[cfg(feature = "otp-qr")]
+    pub fn from_qr_image(image: &[u8]) -> Result<Self, StoreError> {
+        let image = image::load_from_memory(image)
+            .map_err(|err| StoreError::QrImage(Box::new(err)))?
+            .to_luma8();
+        let mut image = rqrr::PreparedImage::prepare(image);
+
+        let mut found = None;
+        for grid in image.detect_grids() {
+            let (_, content) = grid
+                .decode()
+                .map_err(|err| StoreError::QrImage(Box::new(err)))?;
+            if content.starts_with("otpauth://") {
+                found = Some(content);
+                break;
+            }
+        }
+        let uri = found.ok_or_else(|| invalid("no otpauth QR code found in image"))?;
+        Self::from_uri(&uri)
+    }
+
+    pub fn kind(&self) -> OtpKind {
+        self.kind
+    }
+
+    pub fn algorithm(&self) -> OtpAlgorithm {
+        self.algorithm
+    }
+
+    pub fn digits(&self) -> u32 {
+        self.digits
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn issuer(&self) -> Option<&str> {
+        self.issuer.as_deref()
+    }
+
+    pub fn to_uri(&self) -> String {
+        self.url.to_string()
+    }
+
+    pub fn code(&self) -> OtpCode {
+        self.code_at(SystemTime::now())
+    }
+
+    pub fn code_at(&self, time: SystemTime) -> OtpCode {
+        match self.kind {
+            OtpKind::Totp { period } => {
+                let seconds = time
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                OtpCode {
+                    code: self.generate(seconds / period),
+                    remaining: Some(Duration::from_secs(period - seconds % period)),
+                }
+            }
+            OtpKind::Hotp { counter } => OtpCode {
+                code: self.generate(counter),
+                remaining: None,
+            },
+        }
+    }
+
+    // only touch the counter parameter so the rest of the URI stays as pass-otp wrote it
+    pub(crate) fn increment(&self) -> Option<Self> {
+        let counter = match self.kind {
+            OtpKind::Hotp { counter } => counter + 1,
+            OtpKind::Totp { .. } => return None,
+        };
+        let query = self
+            .url
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .map(|pair| {
+                if pair.starts_with("counter=") {
+                    format!("counter={}", counter)
+                } else {
+                    pair.to_owned()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let mut next = self.clone();
+        next.url.set_query(Some(&query));
+        next.kind = OtpKind::Hotp { counter };
+        Some(next)
+    }
+
+    // RFC 4226 dynamic truncation, TOTP (RFC 6238) only differs in how the counter is derived
+    fn generate(&self, counter: u64) -> String {
+        let message = counter.to_be_bytes();
+        let digest = match self.algorithm {
+            OtpAlgorithm::Sha1 => Hmac::<Sha1>::new_from_slice(&self.secret).map(|mut mac| {
+                mac.update(&message);
+                mac.finalize().into_bytes().to_vec()
+            }),
+            OtpAlgorithm::Sha256 => Hmac::<Sha256>::new_from_slice(&self.secret).map(|mut mac| {
+                mac.update(&message);
+                mac.finalize().into_bytes().to_vec()
+            }),
+            OtpAlgorithm::Sha512 => Hmac::<Sha512>::new_from_slice(&self.secret).map(|mut mac| {
+                mac.update(&message);
+                mac.finalize().into_bytes().to_vec()
+            }),
+        }
+        .expect("HMAC accepts keys of any length");
+
+        let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            digest[offset] & 0x7f,
+            digest[offset + 1],
+            digest[offset + 2],
+            digest[offset + 3],
+        ]);
+        let code = binary % 10u32.pow(self.digits);
+        format!("{:0width$}", code, width = self.digits as usize)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OtpBuilder {
+    kind: OtpKind,
+    secret: String,
+    account: String,
+    issuer: Option<String>,
+    algorithm: OtpAlgorithm,
+    digits: u32,
+}
+
+impl OtpBuilder {
+    pub fn totp<S: Into<String>, A: Into<String>>(secret: S, account: A) -> Self {
+        Self::new(OtpKind::Totp { period: 30 }, secret.into(), account.into())
+    }
+
+    pub fn hotp<S: Into<String>, A: Into<String>>(secret: S, account: A, counter: u64) -> Self {
+        Self::new(OtpKind::Hotp { counter }, secret.into(), account.into())
+    }
+
+    fn new(kind: OtpKind, secret: String, account: String) -> Self {
+        Self {
+            kind,
+            secret,
+            account,
+            issuer: None,
+            algorithm: OtpAlgorithm::Sha1,
+            digits: 6,
+        }
+    }
+
+    pub fn issuer<I: Into<String>>(&mut self, issuer: I) -> &mut Self {
+        self.issuer = Some(issuer.into());
+        self
+    }
+
+    pub fn algorithm(&mut self, algorithm: OtpAlgorithm) -> &mut Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    pub fn digits(&mut self, digits: u32) -> &mut Self {
+        self.digits = digits;
+        self
+    }
+
+    // has no effect on HOTP secrets
+    pub fn period(&mut self, period: u64) -> &mut Self {
+        if let OtpKind::Totp { .. } = self.kind {
+            self.kind = OtpKind::Totp { period };
+        }
+        self
+    }
+
+    // writes the same URI layout as `pass otp insert` and validates it by parsing it again
+    pub fn build(&self) -> Result<Otp, StoreError> {
+        let encode = |value: &str| utf8_percent_encode(value, NON_ALPHANUMERIC).to_string();
+
+        let (kind, kind_param) = match self.kind {
+            OtpKind::Totp { period: 30 } => ("totp", None),
+            OtpKind::Totp { period } => ("totp", Some(format!("&period={}", period))),
+            OtpKind::Hotp { counter } => ("hotp", Some(format!("&counter={}", counter))),
+        };
+        let mut uri = match &self.issuer {
+            Some(issuer) => format!(
+                "otpauth://{}/{}:{}?secret={}&issuer={}",
+                kind,
+                encode(issuer),
+                encode(&self.account),
+                encode(&normalize_secret(&self.secret)),
+                encode(issuer),
+            ),
+            None => format!(
+                "otpauth://{}/{}?secret={}",
+                kind,
+                encode(&self.account),
+                encode(&normalize_secret(&self.secret)),
+            ),
+        };
+        if let Some(kind_param) = kind_param {
+            uri.push_str(&kind_param);
+        }
+        if self.algorithm != OtpAlgorithm::Sha1 {
+            uri.push_str(&format!("&algorithm={}", self.algorithm.name()));
+        }
+        if self.digits != 6 {
+            uri.push_str(&format!("&digits={}", self.digits));
+        }
+
+        Otp::from_uri(&uri)
+    }
+}
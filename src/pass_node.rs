@@ -38,4 +38,12 @@ impl PassNode {
             Self::NormalFile { ref path, .. } => path,
         }
     }
+
+    pub(crate) fn path_mut(&mut self) -> &mut PathBuf {
+        match self {
+            Self::Directory { ref mut path, .. } => path,
+            Self::Password { ref mut path, .. } => path,
+            Self::NormalFile { ref mut path, .. } => path,
+        }
+    }
 }
@@ -3,7 +3,15 @@ use std::path::PathBuf;
 
 use id_tree::NodeId;
 
-use crate::{DecryptedPassword, Directory, MutEntry, PassNode, Store, StoreError, Traversal};
+use git2::Oid;
+
+use crate::{
+    DecryptedPassword, Directory, IntoStoreError, MutEntry, PassNode, PassphraseProvider,
+    Revision, Store, StoreError, Traversal,
+};
+
+#[cfg(feature = "qr-codes")]
+use crate::QrCode;
 
 #[derive(Debug)]
 pub struct Password {
@@ -11,15 +19,23 @@ pub struct Password {
     path: PathBuf,
     node_id: NodeId,
     root: PathBuf,
+    passphrase_provider: PassphraseProvider,
 }
 
 impl Password {
-    pub(crate) fn new(name: String, path: PathBuf, root: PathBuf, node_id: NodeId) -> Self {
+    pub(crate) fn new(
+        name: String,
+        path: PathBuf,
+        root: PathBuf,
+        node_id: NodeId,
+        passphrase_provider: PassphraseProvider,
+    ) -> Self {
         Self {
             name,
             path,
             root,
             node_id,
+            passphrase_provider,
         }
     }
 
@@ -56,7 +72,42 @@ impl Password {
     }
 
     pub fn decrypt(&self) -> Result<DecryptedPassword, StoreError> {
-        DecryptedPassword::from_path(&self.path)
+        DecryptedPassword::from_path(&self.path, &self.passphrase_provider)
+    }
+
+    fn relative_path(&self) -> &Path {
+        self.path
+            .strip_prefix(&self.root)
+            .expect("Password not stored inside this password store!")
+    }
+
+    // newest first, renames of the password are followed
+    pub fn history(&self, store: &Store) -> Result<Vec<Revision>, StoreError> {
+        let git = store.git_ref().ok_or(StoreError::NoGit)?;
+        git.history(self.relative_path()).with_store_error("history")
+    }
+
+    pub fn decrypt_at(&self, store: &Store, commit: Oid) -> Result<DecryptedPassword, StoreError> {
+        let git = store.git_ref().ok_or(StoreError::NoGit)?;
+        let path = git
+            .path_at(self.relative_path(), commit)
+            .with_store_error("history")?
+            .ok_or_else(|| {
+                StoreError::NotInRevision(self.relative_path().to_owned(), commit.to_string())
+            })?;
+        let encrypted = git
+            .blob_at(commit, &path)
+            .with_store_error("find password in commit")?
+            .ok_or_else(|| StoreError::NotInRevision(path, commit.to_string()))?;
+
+        DecryptedPassword::from_encrypted(&encrypted, &self.path, &self.passphrase_provider)
+    }
+
+    #[cfg(feature = "qr-codes")]
+    pub fn qr_code(&self) -> Result<QrCode, StoreError> {
+        let encrypted =
+            std::fs::read(&self.path).with_store_error(self.path.display().to_string())?;
+        QrCode::new(encrypted)
     }
 
     pub fn make_mut(self, store: &mut Store) -> MutPassword {
@@ -120,16 +171,16 @@ impl<'a> MutPassword<'a> {
         self.to_entry().rename(name)
     }
 
-    pub fn move_to(&mut self, directory: &Directory) {
+    pub fn move_to(&mut self, directory: &Directory) -> Result<(), StoreError> {
         self.to_entry().move_to(directory)
     }
 
-    pub fn copy_to(&mut self, directory: &Directory) {
+    pub fn copy_to(&mut self, directory: &Directory) -> Result<(), StoreError> {
         self.to_entry().copy_to(directory)
     }
 
     pub fn decrypt(&self) -> Result<DecryptedPassword, StoreError> {
-        DecryptedPassword::from_path(&self.data().path())
+        DecryptedPassword::from_path(&self.data().path(), self.store.passphrase_provider())
     }
 
     pub fn make_immut(self) -> Password {
@@ -138,6 +189,7 @@ impl<'a> MutPassword<'a> {
             self.path().to_owned(),
             self.store.location().to_owned(),
             self.node_id,
+            self.store.passphrase_provider().clone(),
         )
     }
 }
@@ -0,0 +1,71 @@
+use std::fmt::{Debug, Formatter};
+
+use qrcode::{
+    render::{svg, unicode},
+    Color,
+};
+
+use crate::StoreError;
+
+const PNG_MODULE_SIZE: usize = 8;
+const PNG_QUIET_ZONE: usize = 4;
+
+fn render_error<E>(err: E) -> StoreError
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    StoreError::QrCode(Box::new(err))
+}
+
+#[derive(Clone)]
+pub struct QrCode {
+    code: qrcode::QrCode,
+}
+
+impl Debug for QrCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QrCode")
+            .field("width", &self.code.width())
+            .finish()
+    }
+}
+
+impl QrCode {
+    pub(crate) fn new<D: AsRef<[u8]>>(data: D) -> Result<Self, StoreError> {
+        let code = qrcode::QrCode::new(data).map_err(render_error)?;
+        Ok(Self { code })
+    }
+
+    pub fn to_terminal(&self) -> String {
+        self.code.render::<unicode::Dense1x2>().build()
+    }
+
+    pub fn to_svg(&self) -> String {
+        self.code.render::<svg::Color>().build()
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>, StoreError> {
+        let modules = self.code.width();
+        let size = (modules + 2 * PNG_QUIET_ZONE) * PNG_MODULE_SIZE;
+        let mut pixels = vec![0xff; size * size];
+        for (i, color) in self.code.to_colors().into_iter().enumerate() {
+            if color == Color::Dark {
+                let x = (i % modules + PNG_QUIET_ZONE) * PNG_MODULE_SIZE;
+                let y = (i / modules + PNG_QUIET_ZONE) * PNG_MODULE_SIZE;
+                for row in y..y + PNG_MODULE_SIZE {
+                    pixels[row * size + x..row * size + x + PNG_MODULE_SIZE].fill(0x00);
+                }
+            }
+        }
+
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, size as u32, size as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(render_error)?;
+        writer.write_image_data(&pixels).map_err(render_error)?;
+        writer.finish().map_err(render_error)?;
+
+        Ok(png)
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    fs::{self, File, Permissions},
+    io::{self, Read, Write},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use tempfile::{NamedTempFile, TempPath};
+
+use crate::{
+    decrypt_password_file, encrypt_password, search_gpg_ids, IntoStoreError, Store, StoreError,
+};
+
+pub(crate) struct DecryptedFile {
+    path: PathBuf,
+    content: Vec<u8>,
+}
+
+struct StagedFile {
+    path: PathBuf,
+    content: Vec<u8>,
+    encrypted: Vec<u8>,
+    reencrypted: TempPath,
+    backup: TempPath,
+}
+
+// decrypting everything before the recipients change lets a missing secret key fail early
+pub(crate) fn decrypt_files(
+    store: &Store,
+    paths: &[PathBuf],
+) -> Result<Vec<DecryptedFile>, StoreError> {
+    paths
+        .iter()
+        .map(|path| {
+            Ok(DecryptedFile {
+                path: path.clone(),
+                content: decrypt_password_file(path, store.passphrase_provider())?,
+            })
+        })
+        .collect()
+}
+
+fn invalid_data(path: &Path, reason: &str) -> StoreError {
+    StoreError::Io(
+        path.display().to_string(),
+        io::Error::new(io::ErrorKind::InvalidData, reason.to_owned()),
+    )
+}
+
+// the staged ciphertext must decrypt to the original content for exactly the current gpg-ids
+fn check_recipients(
+    path: &Path,
+    encrypted: &[u8],
+    content: &[u8],
+    store: &Store,
+) -> Result<(), StoreError> {
+    let passphrase_provider = store.passphrase_provider();
+    let mut ctx = passphrase_provider
+        .context()
+        .with_store_error("creating OpenPGP context")?;
+    let gpg_ids = search_gpg_ids(path, &mut ctx, store.signing_keys())?;
+
+    // gpg only reveals the recipients of messages it can decrypt, which is impossible once
+    // the own key was removed from the gpg-ids
+    let decryptable = gpg_ids.iter().any(|key| {
+        key.fingerprint()
+            .map_or(false, |fingerprint| ctx.get_secret_key(fingerprint).is_ok())
+    });
+    if !decryptable {
+        return Ok(());
+    }
+
+    let mut decrypted = Vec::new();
+    let result = passphrase_provider
+        .provide(&mut ctx, |ctx| ctx.decrypt(encrypted, &mut decrypted))
+        .with_store_error(path.display().to_string())?;
+    if decrypted != content {
+        return Err(invalid_data(path, "Reencrypted password does not match the original"));
+    }
+
+    let recipients = result
+        .recipients()
+        .filter_map(|recipient| recipient.key_id().ok().map(str::to_owned))
+        .collect::<Vec<_>>();
+    let is_gpg_id = |key_id: &str| {
+        gpg_ids.iter().any(|key| {
+            key.subkeys()
+                .any(|subkey| subkey.id().map_or(false, |id| id.eq_ignore_ascii_case(key_id)))
+        })
+    };
+    let covered = gpg_ids.iter().all(|key| {
+        key.subkeys().any(|subkey| {
+            subkey.id().map_or(false, |id| {
+                recipients.iter().any(|key_id| id.eq_ignore_ascii_case(key_id))
+            })
+        })
+    });
+    if !covered || !recipients.iter().all(|key_id| is_gpg_id(key_id)) {
+        return Err(invalid_data(path, "Reencrypted password is not encrypted for the gpg-ids"));
+    }
+
+    Ok(())
+}
+
+fn stage(file: DecryptedFile, store: &Store) -> Result<StagedFile, StoreError> {
+    let path = file.path;
+    let display = path.display().to_string();
+    let parent = path.parent().expect("Password has no parent directory");
+    let encrypted = encrypt_password(&path, &file.content, store.signing_keys())?;
+
+    let mut reencrypted = NamedTempFile::new_in(parent).with_store_error(display.clone())?;
+    reencrypted
+        .write_all(&encrypted)
+        .with_store_error(display.clone())?;
+    reencrypted
+        .as_file()
+        .set_permissions(Permissions::from_mode(0o666 & !store.umask()))
+        .with_store_error(display.clone())?;
+    reencrypted
+        .as_file()
+        .sync_all()
+        .with_store_error(display.clone())?;
+
+    // validate what actually reached the disk, not what was meant to be written
+    let mut written = Vec::new();
+    File::open(reencrypted.path())
+        .and_then(|mut f| f.read_to_end(&mut written))
+        .with_store_error(display.clone())?;
+    if written != encrypted {
+        return Err(invalid_data(&path, "Reencrypted password is corrupted"));
+    }
+    check_recipients(&path, &written, &file.content, store)?;
+
+    let backup = NamedTempFile::new_in(parent)
+        .with_store_error(display.clone())?
+        .into_temp_path();
+    fs::copy(&path, &backup).with_store_error(display)?;
+
+    Ok(StagedFile {
+        path,
+        content: file.content,
+        encrypted,
+        reencrypted: reencrypted.into_temp_path(),
+        backup,
+    })
+}
+
+// either every password is encrypted for the current gpg-ids or none of them is touched,
+// returns the reencrypted paths
+pub(crate) fn reencrypt_files(
+    store: &mut Store,
+    files: Vec<DecryptedFile>,
+) -> Result<Vec<PathBuf>, StoreError> {
+    // dropping staged files removes their temporary copies, so failures here change nothing
+    let staged = files
+        .into_iter()
+        .map(|file| stage(file, store))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut swapped = Vec::new();
+    for file in staged {
+        let StagedFile {
+            path,
+            content,
+            encrypted,
+            reencrypted,
+            backup,
+        } = file;
+        match reencrypted.persist(&path) {
+            Ok(()) => swapped.push((path, content, encrypted, backup)),
+            Err(err) => {
+                let err = StoreError::Io(path.display().to_string(), err.error);
+                // a failed rollback is worse than the failed swap, report it instead
+                let mut result = Err(err);
+                for (path, _, _, backup) in swapped.into_iter().rev() {
+                    if let Err(err) = backup.persist(&path) {
+                        result = Err(StoreError::Io(path.display().to_string(), err.error));
+                    }
+                }
+                return result;
+            }
+        }
+    }
+
+    // the passwords are swapped already, a stale search index must not turn that into a failure
+    let mut paths = Vec::new();
+    for (path, content, encrypted, _) in swapped {
+        let updated = store.search_index().map_or(Ok(()), |index| {
+            index.update(&path, &String::from_utf8_lossy(&content), &encrypted)
+        });
+        if let Err(err) = updated {
+            store.push_error(err);
+        }
+        paths.push(path);
+    }
+    store.flush_search_index();
+
+    Ok(paths)
+}
@@ -0,0 +1,195 @@
+use git2::Oid;
+use gpgme::{Context, Protocol};
+use std::{
+    ffi::OsString,
+    fs::{self, DirBuilder, Permissions},
+    io::Write,
+    os::unix::fs::{DirBuilderExt, PermissionsExt},
+    path::{Path, PathBuf},
+};
+use tempfile::{NamedTempFile, TempPath};
+
+use crate::{
+    decrypt_password_data, encrypt_password, search_gpg_fingerprints, IntoStoreError, Store,
+    StoreError,
+};
+
+pub(crate) struct Restored {
+    pub(crate) root: PathBuf,
+    pub(crate) files: Vec<PathBuf>,
+    pub(crate) reencrypted: usize,
+}
+
+// recipients are never restored, the current .gpg-id files decide who can read restored passwords
+fn is_gpg_id(path: &Path) -> bool {
+    matches!(
+        path.file_name().and_then(|name| name.to_str()),
+        Some(".gpg-id") | Some(".gpg-id.sig")
+    )
+}
+
+// None if a recipient is no longer known, which is treated like changed recipients
+fn gpg_id_fingerprints(gpg_id: &[u8], ctx: &mut Context) -> Option<Vec<String>> {
+    let mut fingerprints = String::from_utf8_lossy(gpg_id)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let key = ctx.get_key(line).ok()?;
+            key.fingerprint().ok().map(str::to_owned)
+        })
+        .collect::<Option<Vec<_>>>()?;
+    fingerprints.sort();
+
+    Some(fingerprints)
+}
+
+struct StagedFile {
+    staged: TempPath,
+    backup: Option<TempPath>,
+}
+
+fn stage(
+    target: &Path,
+    content: &[u8],
+    umask: u32,
+    created_dirs: &mut Vec<PathBuf>,
+) -> Result<StagedFile, StoreError> {
+    let display = target.display().to_string();
+    let parent = target.parent().expect("Restored file has no parent");
+    if let Some(missing) = parent.ancestors().take_while(|dir| !dir.exists()).last() {
+        created_dirs.push(missing.to_owned());
+    }
+    DirBuilder::new()
+        .recursive(true)
+        .mode(0o777 & !umask)
+        .create(parent)
+        .with_store_error(parent.display().to_string())?;
+
+    let mut staged = NamedTempFile::new_in(parent).with_store_error(display.clone())?;
+    staged.write_all(content).with_store_error(display.clone())?;
+    staged
+        .as_file()
+        .set_permissions(Permissions::from_mode(0o666 & !umask))
+        .with_store_error(display.clone())?;
+    staged.as_file().sync_all().with_store_error(display.clone())?;
+
+    let backup = if target.exists() {
+        let backup = NamedTempFile::new_in(parent)
+            .with_store_error(display.clone())?
+            .into_temp_path();
+        fs::copy(target, &backup).with_store_error(display)?;
+        Some(backup)
+    } else {
+        None
+    };
+
+    Ok(StagedFile {
+        staged: staged.into_temp_path(),
+        backup,
+    })
+}
+
+// best effort, the directories only ever contained staged files
+fn remove_dirs(dirs: &[PathBuf]) {
+    for dir in dirs.iter().rev() {
+        let _ = fs::remove_dir_all(dir);
+    }
+}
+
+pub(crate) fn restore_files(
+    store: &Store,
+    path: &Path,
+    commit: Oid,
+) -> Result<Restored, StoreError> {
+    let git = store.git_ref().ok_or(StoreError::NoGit)?;
+
+    let mut password = OsString::from(path.as_os_str());
+    password.push(".gpg");
+    let password = PathBuf::from(password);
+    let (root, mut files) = match git
+        .files_at(commit, &password)
+        .with_store_error("find entry in commit")?
+    {
+        files if !files.is_empty() => (password, files),
+        _ => (
+            path.to_owned(),
+            git.files_at(commit, path)
+                .with_store_error("find entry in commit")?,
+        ),
+    };
+    files.retain(|(file, _)| !is_gpg_id(file));
+    if files.is_empty() {
+        return Err(StoreError::NotInRevision(path.to_owned(), commit.to_string()));
+    }
+
+    let mut ctx =
+        Context::from_protocol(Protocol::OpenPgp).with_store_error("creating OpenPGP context")?;
+    // encrypt everything in memory first, so a failure leaves the store untouched
+    let mut restored = Vec::new();
+    let mut reencrypted = 0;
+    for (file, content) in files {
+        let target = store.location().join(&file);
+        let content = if file.extension().map_or(false, |ext| ext == "gpg") {
+            let previous = git
+                .gpg_id_at(commit, &file)
+                .with_store_error("find gpg-id in commit")?
+                .and_then(|gpg_id| gpg_id_fingerprints(&gpg_id, &mut ctx));
+            let current = search_gpg_fingerprints(&target, &mut ctx, store.signing_keys())?;
+            if previous.as_ref() == Some(&current) {
+                content
+            } else {
+                reencrypted += 1;
+                let decrypted =
+                    decrypt_password_data(&content, &target, store.passphrase_provider())?;
+                encrypt_password(&target, &decrypted, store.signing_keys())?
+            }
+        } else {
+            content
+        };
+        restored.push((target, content));
+    }
+
+    // every file is written next to its target first and only swapped in once all writes
+    // succeeded, so a failure part way through leaves the store untouched
+    let mut created_dirs = Vec::new();
+    let staged = restored
+        .iter()
+        .map(|(target, content)| stage(target, content, store.umask(), &mut created_dirs))
+        .collect::<Result<Vec<_>, _>>();
+    let staged = match staged {
+        Ok(staged) => staged,
+        Err(err) => {
+            remove_dirs(&created_dirs);
+            return Err(err);
+        }
+    };
+
+    let mut swapped = Vec::new();
+    for ((target, _), file) in restored.iter().zip(staged) {
+        let StagedFile { staged, backup } = file;
+        if let Err(err) = staged.persist(target) {
+            let err = StoreError::Io(target.display().to_string(), err.error);
+            // a failed rollback is worse than the failed swap, report it instead
+            let mut result = Err(err);
+            for (target, backup) in swapped.into_iter().rev() {
+                let rolled_back = match backup {
+                    Some(backup) => backup.persist(target).map_err(|err| err.error),
+                    None => fs::remove_file(target),
+                };
+                if let Err(err) = rolled_back {
+                    result = Err(StoreError::Io(target.display().to_string(), err));
+                }
+            }
+            remove_dirs(&created_dirs);
+            return result;
+        }
+        swapped.push((target, backup));
+    }
+
+    Ok(Restored {
+        root: store.location().join(root),
+        files: restored.into_iter().map(|(target, _)| target).collect(),
+        reencrypted,
+    })
+}
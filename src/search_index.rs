@@ -0,0 +1,320 @@
+use git2::{ObjectType, Oid};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::DirBuilder,
+    os::unix::fs::DirBuilderExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use gpgme::{Context, Key, Protocol};
+
+use crate::{
+    decrypt_password_file, search_gpg_ids, search_parallel, write_encrypted_password,
+    IntoStoreError, PassphraseProvider, Password, Position, StoreError,
+};
+
+const HEADER: &str = "libpass search index 1";
+
+struct IndexRecord {
+    blob: String,
+    lines: Vec<String>,
+    keys: Vec<String>,
+}
+
+impl IndexRecord {
+    // the passphrase on the first line is never written to the index
+    fn new<'a>(blob: String, mut lines: impl Iterator<Item = &'a str>, path: &Path) -> Self {
+        lines.next();
+        let lines = lines.map(str::to_owned).collect::<Vec<_>>();
+        let keys = Self::keys(&lines, path);
+        Self { blob, lines, keys }
+    }
+
+    #[cfg(feature = "parsed-passwords")]
+    fn keys(lines: &[String], path: &Path) -> Vec<String> {
+        // the grammar expects a passphrase in front of the entries
+        let mut content = vec![String::from("-")];
+        content.extend(lines.iter().cloned());
+        let parsed =
+            crate::parsed::DecryptedPassword::from_lines(content, Vec::new(), path.to_owned());
+        let mut keys = parsed
+            .map(|parsed| {
+                parsed
+                    .all_entries()
+                    .map(|(_, (key, _))| key.to_owned())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
+    #[cfg(not(feature = "parsed-passwords"))]
+    fn keys(_lines: &[String], _path: &Path) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+pub(crate) struct SearchIndex {
+    path: PathBuf,
+    root: PathBuf,
+    umask: u32,
+    signing_keys: Vec<String>,
+    passphrase_provider: PassphraseProvider,
+    records: BTreeMap<String, IndexRecord>,
+    dirty: bool,
+}
+
+impl fmt::Debug for SearchIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SearchIndex")
+            .field("path", &self.path.display().to_string())
+            .field("records", &self.records.len())
+            .finish()
+    }
+}
+
+impl SearchIndex {
+    pub(crate) fn new(
+        path: PathBuf,
+        root: PathBuf,
+        umask: u32,
+        signing_keys: Vec<String>,
+        passphrase_provider: PassphraseProvider,
+    ) -> Self {
+        Self {
+            path,
+            root,
+            umask,
+            signing_keys,
+            passphrase_provider,
+            records: BTreeMap::new(),
+            dirty: false,
+        }
+    }
+
+    fn name(root: &Path, path: &Path) -> String {
+        path.strip_prefix(root)
+            .unwrap_or(path)
+            .with_extension("")
+            .display()
+            .to_string()
+    }
+
+    pub(crate) fn load(&mut self) -> Result<(), StoreError> {
+        if !self.path.exists() {
+            return Ok(());
+        }
+
+        let content = decrypt_password_file(&self.path, &self.passphrase_provider)?;
+        self.records = Self::parse(&String::from_utf8_lossy(&content))
+            .ok_or_else(|| StoreError::InvalidSearchIndex(self.path.clone()))?;
+        Ok(())
+    }
+
+    fn parse(content: &str) -> Option<BTreeMap<String, IndexRecord>> {
+        let mut lines = content.lines();
+        if lines.next()? != HEADER {
+            return None;
+        }
+
+        let mut records = BTreeMap::new();
+        let mut current: Option<(String, IndexRecord)> = None;
+        for line in lines {
+            let (kind, rest) = line.split_once('\t')?;
+            match kind {
+                "P" => {
+                    if let Some((name, record)) = current.take() {
+                        records.insert(name, record);
+                    }
+                    let (blob, name) = rest.split_once('\t')?;
+                    let record = IndexRecord {
+                        blob: blob.to_owned(),
+                        lines: Vec::new(),
+                        keys: Vec::new(),
+                    };
+                    current = Some((name.to_owned(), record));
+                }
+                "L" => current.as_mut()?.1.lines.push(rest.to_owned()),
+                "K" => current.as_mut()?.1.keys.push(rest.to_owned()),
+                _ => return None,
+            }
+        }
+        if let Some((name, record)) = current {
+            records.insert(name, record);
+        }
+
+        Some(records)
+    }
+
+    fn serialize(&self) -> String {
+        let mut content = format!("{}\n", HEADER);
+        for (name, record) in &self.records {
+            content.push_str(&format!("P\t{}\t{}\n", record.blob, name));
+            for line in &record.lines {
+                content.push_str(&format!("L\t{}\n", line));
+            }
+            for key in &record.keys {
+                content.push_str(&format!("K\t{}\n", key));
+            }
+        }
+        content
+    }
+
+    // the index holds lines of passwords whose .gpg-id may exclude some recipients of the
+    // store's root directory, so only the local user's keys may decrypt it: the root gpg-ids
+    // with a secret key in the keyring or, if there are none, every own key able to encrypt
+    fn recipients(&self, ctx: &mut Context) -> Result<Vec<Key>, StoreError> {
+        let gpg_ids = search_gpg_ids(&self.root, ctx, &self.signing_keys)?;
+        let mut own = Vec::new();
+        for key in gpg_ids {
+            let has_secret = key
+                .fingerprint()
+                .map_or(false, |fingerprint| ctx.get_secret_key(fingerprint).is_ok());
+            if has_secret {
+                own.push(key);
+            }
+        }
+        if own.is_empty() {
+            own = ctx
+                .secret_keys()
+                .with_store_error("listing secret keys")?
+                .filter_map(Result::ok)
+                .filter(|key| key.can_encrypt())
+                .collect();
+        }
+
+        if own.is_empty() {
+            return Err(StoreError::NoSecretKey(self.path.clone()));
+        }
+        Ok(own)
+    }
+
+    fn save(&self) -> Result<(), StoreError> {
+        if let Some(parent) = self.path.parent() {
+            DirBuilder::new()
+                .recursive(true)
+                .mode(0o777 & !self.umask)
+                .create(parent)
+                .with_store_error(parent.display().to_string())?;
+        }
+
+        let mut ctx = Context::from_protocol(Protocol::OpenPgp)
+            .with_store_error("creating OpenPGP context")?;
+        let recipients = self.recipients(&mut ctx)?;
+        let mut encrypted = Vec::new();
+        let result = ctx
+            .encrypt(&recipients, self.serialize().as_bytes(), &mut encrypted)
+            .with_store_error(self.path.display().to_string())?;
+        if result.invalid_recipients().count() > 0 {
+            return Err(StoreError::Gpg(
+                "Could not encrypt search index for all own keys".to_owned(),
+                gpgme::Error::BAD_PUBKEY,
+            ));
+        }
+        write_encrypted_password(&self.path, &encrypted, self.umask)
+    }
+
+    pub(crate) fn update(
+        &mut self,
+        path: &Path,
+        content: &str,
+        encrypted: &[u8],
+    ) -> Result<(), StoreError> {
+        let blob = Oid::hash_object(ObjectType::Blob, encrypted)
+            .with_store_error("hash password file")?
+            .to_string();
+        let record = IndexRecord::new(blob, content.lines(), path);
+        self.records.insert(Self::name(&self.root, path), record);
+        self.dirty = true;
+        Ok(())
+    }
+
+    // updates only change the records in memory, encrypting the whole index once per operation
+    pub(crate) fn flush(&mut self) -> Result<(), StoreError> {
+        if !self.dirty {
+            return Ok(());
+        }
+        self.save()?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    // only passwords whose git blob id changed since the last refresh get decrypted again
+    pub(crate) fn refresh(
+        &mut self,
+        passwords: Vec<Password>,
+        threads: Option<usize>,
+    ) -> (Vec<Password>, Vec<StoreError>) {
+        let known = Arc::new(
+            self.records
+                .iter()
+                .map(|(name, record)| (name.clone(), record.blob.clone()))
+                .collect::<HashMap<_, _>>(),
+        );
+        let root = self.root.clone();
+        let (results, mut errors) = search_parallel(passwords, threads, move |password| {
+            let name = Self::name(&root, password.path());
+            let blob = Oid::hash_file(ObjectType::Blob, password.path())
+                .with_store_error("hash password file")?
+                .to_string();
+            if known.get(&name) == Some(&blob) {
+                return Ok(Some((name, None)));
+            }
+
+            let decrypted = password.decrypt()?;
+            let record = IndexRecord::new(blob, decrypted.lines(), password.path());
+            Ok(Some((name, Some(record))))
+        });
+
+        let mut changed = false;
+        let mut records = BTreeMap::new();
+        let mut passwords = Vec::new();
+        for (password, (name, record)) in results {
+            match record {
+                Some(record) => {
+                    changed = true;
+                    records.insert(name, record);
+                }
+                None => {
+                    if let Some(record) = self.records.remove(&name) {
+                        records.insert(name, record);
+                    }
+                }
+            }
+            passwords.push(password);
+        }
+        // whatever is left belongs to passwords that vanished or cannot be decrypted anymore
+        changed |= !self.records.is_empty();
+        self.records = records;
+
+        self.dirty |= changed;
+        if let Err(err) = self.flush() {
+            errors.push(err);
+        }
+        (passwords, errors)
+    }
+
+    pub(crate) fn lines(
+        &self,
+        password: &Password,
+    ) -> Option<impl Iterator<Item = (Position, &str)>> {
+        let record = self.records.get(&Self::name(&self.root, password.path()))?;
+        Some(
+            record
+                .lines
+                .iter()
+                .enumerate()
+                .map(|(position, line)| (position + 1, line.as_str())),
+        )
+    }
+
+    pub(crate) fn keys(&self, password: &Password) -> Option<&[String]> {
+        self.records
+            .get(&Self::name(&self.root, password.path()))
+            .map(|record| record.keys.as_slice())
+    }
+}
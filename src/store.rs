@@ -1,14 +1,20 @@
+use std::fs::{DirBuilder, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
 use std::path::PathBuf;
+use std::sync::Mutex;
 use std::{env, fs, path::Path};
 
 use directories::BaseDirs;
+use git2::{ErrorCode, Oid};
 use id_tree::{InsertBehavior, Node, NodeId, Tree};
 
 use crate::{
-    DecryptedPassword, Directory, DirectoryInserter, Entries, Entry, Git, IntoStoreError, Location,
-    MatchedEntries, MatchedPasswords, MutDirectory, MutEntry, MutPassword, PassNode,
-    PassphraseProvider, Password, PasswordInserter, SigningKey, Sorting, StoreError, StoreErrors,
-    TraversalOrder, Umask,
+    fuzzy_find, grep_parallel, restore_files, sign_gpg_id, CredentialsProvider, DecryptedPassword,
+    Directory, DirectoryInserter, Entries, Entry, FindOptions, FuzzyMatch, Git, GpgKeyId,
+    GrepOptions, GrepResults, IndexLocation, IntoStoreError, Location, MatchTarget, MatchedEntries,
+    MatchedPasswords, MutDirectory, MutEntry, MutPassword, PassNode, PassphraseProvider, Password,
+    PasswordInserter, SearchIndex, Sorting, StoreBuilder, StoreError, StoreErrors, TraversalOrder,
 };
 
 #[derive(Debug)]
@@ -17,28 +23,67 @@ pub struct Store {
     pub(crate) tree: Tree<PassNode>,
     errors: Vec<StoreError>,
     git: Option<Git>,
+    umask: u32,
+    signing_keys: Vec<String>,
+    passphrase_provider: PassphraseProvider,
+    credentials_provider: CredentialsProvider,
+    search_index: Option<Mutex<SearchIndex>>,
 }
 
 impl Store {
-    pub(crate) fn init(
-        _location: Location,
-        _passphrase_provider: PassphraseProvider,
-        _umask: Umask,
-        _signing_key: SigningKey,
-        _sorting: Sorting,
-        _key_id: &str,
-    ) -> Result<Self, StoreError> {
-        todo!();
-    }
-
-    pub(crate) fn open(
-        location: Location,
-        _passphrase_provider: PassphraseProvider,
-        _umask: Umask,
-        _signing_key: SigningKey,
-        sorting: Sorting,
-    ) -> Result<Self, StoreError> {
-        let path = match location {
+    pub(crate) fn init(builder: &StoreBuilder, key_id: &str) -> Result<Self, StoreError> {
+        let path = Self::resolve_location(builder.location.clone())?;
+        if path.exists() && !path.is_dir() {
+            return Err(StoreError::NoDirectory(path));
+        }
+        if path.join(".gpg-id").exists() {
+            return Err(StoreError::AlreadyExists(path));
+        }
+
+        let mask = builder.umask.resolve()?;
+        let gpg_id = GpgKeyId::new(key_id).with_store_error("GPG ID not found")?;
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o777 & !mask)
+            .create(&path)
+            .with_store_error(path.display().to_string())?;
+
+        let gpg_id_path = path.join(".gpg-id");
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o666 & !mask)
+            .open(&gpg_id_path)
+            .with_store_error(gpg_id_path.display().to_string())?;
+        file.write_all(format!("{}\n", gpg_id.id()).as_bytes())
+            .with_store_error(gpg_id_path.display().to_string())?;
+        drop(file);
+        sign_gpg_id(
+            &gpg_id_path,
+            &builder.signing_key.resolve(),
+            mask,
+            &builder.passphrase_provider,
+        )?;
+
+        Self::open(builder.clone().location(path))
+    }
+
+    pub(crate) fn clone(builder: &StoreBuilder, url: &str) -> Result<Self, StoreError> {
+        let path = Self::resolve_location(builder.location.clone())?;
+        Git::clone(
+            url,
+            &path,
+            builder.passphrase_provider.clone(),
+            builder.credentials_provider.clone(),
+            builder.signing_key.resolve(),
+        )
+        .with_store_error("clone repository")?;
+
+        Self::open(builder.clone().location(path))
+    }
+
+    fn resolve_location(location: Location) -> Result<PathBuf, StoreError> {
+        match location {
             Location::Automatic => env::var("PASSWORD_STORE_DIR")
                 .with_store_error("PASSWORD_STORE_DIR")
                 .map(|password_store_dir| Path::new(&password_store_dir).to_owned())
@@ -47,9 +92,17 @@ impl Store {
                         .map(|base_dirs| base_dirs.home_dir().join(".password-store"))
                         .ok_or(e)
                         .with_store_error("attempted search in default paths")
-                })?,
-            Location::Manual(path) => path,
-        };
+                }),
+            Location::Manual(path) => Ok(path),
+        }
+    }
+
+    pub(crate) fn open(builder: &StoreBuilder) -> Result<Self, StoreError> {
+        let umask = builder.umask.resolve()?;
+        let signing_keys = builder.signing_key.resolve();
+        let passphrase_provider = builder.passphrase_provider.clone();
+        let credentials_provider = builder.credentials_provider.clone();
+        let path = Self::resolve_location(builder.location.clone())?;
         let path = path
             .canonicalize()
             .with_store_error(path.display().to_string())?;
@@ -61,19 +114,82 @@ impl Store {
         }
 
         let tree = Tree::new();
-        let git = Git::open(&path).with_store_error("open repository")?;
+        let git = Git::open(
+            &path,
+            passphrase_provider.clone(),
+            credentials_provider.clone(),
+            signing_keys.clone(),
+        )
+        .with_store_error("open repository")?;
         let mut me = Self {
             path,
             tree,
             git,
+            umask,
+            signing_keys,
+            passphrase_provider,
+            credentials_provider,
             errors: Vec::new(),
+            search_index: None,
         };
+        me.check_environment();
         me.load_passwords();
-        me.sort(sorting);
+        me.sort(builder.sorting);
+        me.open_search_index(&builder.search_index)?;
 
         Ok(me)
     }
 
+    // a broken index is not fatal, it is rebuilt on the next search
+    fn open_search_index(&mut self, location: &IndexLocation) -> Result<(), StoreError> {
+        if let Some(path) = location.resolve(&self.path)? {
+            let mut index = SearchIndex::new(
+                path,
+                self.path.clone(),
+                self.umask,
+                self.signing_keys.clone(),
+                self.passphrase_provider.clone(),
+            );
+            if let Err(err) = index.load() {
+                self.errors.push(err);
+            }
+            self.search_index = Some(Mutex::new(index));
+        }
+
+        Ok(())
+    }
+
+    // variables of upstream pass which are deliberately not supported
+    fn check_environment(&mut self) {
+        let is_set = |var| env::var_os(var).is_some();
+
+        if is_set("PASSWORD_STORE_KEY") {
+            self.errors.push(StoreError::EnvGpgIdOverride);
+        }
+        if is_set("PASSWORD_STORE_GENERATED_LENGTH") {
+            self.errors.push(StoreError::EnvGeneratedLength);
+        }
+        for var in &[
+            "PASSWORD_STORE_CHARACTER_SET",
+            "PASSWORD_STORE_CHARACTER_SET_NO_SYMBOLS",
+        ] {
+            if is_set(var) {
+                self.errors.push(StoreError::EnvCharacterSet(var));
+            }
+        }
+        if is_set("PASSWORD_STORE_GPG_OPTS") {
+            self.errors.push(StoreError::EnvGpgOptions);
+        }
+        for var in &[
+            "PASSWORD_STORE_ENABLE_EXTENSIONS",
+            "PASSWORD_STORE_EXTENSIONS_DIR",
+        ] {
+            if is_set(var) {
+                self.errors.push(StoreError::EnvExtensions(var));
+            }
+        }
+    }
+
     pub(crate) fn tree(&self) -> &Tree<PassNode> {
         &self.tree
     }
@@ -101,7 +217,7 @@ impl Store {
             .to_string_lossy()
             .as_ref()
         {
-            ".git" | ".gitattributes" | ".gpg-id" => true,
+            ".git" | ".gitattributes" | ".gpg-id" | ".gpg-id.sig" => true,
             _ => false,
         }
     }
@@ -199,6 +315,11 @@ impl Store {
         StoreErrors::new(&self.errors)
     }
 
+    // for failures which must not undo an otherwise completed change
+    pub(crate) fn push_error(&mut self, err: StoreError) {
+        self.errors.push(err);
+    }
+
     pub fn sort(&mut self, sorting: Sorting) {
         if sorting.contains(Sorting::NONE) {
             return;
@@ -222,9 +343,32 @@ impl Store {
         }
     }
 
-    pub fn find<'a, 'b>(&'a self, pattern: &'b str) -> MatchedEntries<'a, 'b> {
+    // case-insensitive substring match against the absolute path of every entry, use
+    // `find_with` to match store-relative paths or names
+    pub fn find<'a>(&'a self, pattern: &str) -> MatchedEntries<'a> {
+        let mut options = FindOptions::default();
+        options.target(MatchTarget::AbsolutePath);
+        self.find_with(pattern, &options).expect("Substring patterns are always valid")
+    }
+
+    pub fn find_with<'a>(
+        &'a self,
+        pattern: &str,
+        options: &FindOptions,
+    ) -> Result<MatchedEntries<'a>, StoreError> {
         MatchedEntries::new(
             pattern,
+            options,
+            &self.path,
+            self.show(".", TraversalOrder::PreOrder)
+                .expect("Root node of internal tree could not be found"),
+        )
+    }
+
+    pub fn fuzzy_find(&self, query: &str) -> Vec<FuzzyMatch> {
+        fuzzy_find(
+            self,
+            query,
             self.show(".", TraversalOrder::PreOrder)
                 .expect("Root node of internal tree could not be found"),
         )
@@ -238,6 +382,33 @@ impl Store {
         )
     }
 
+    pub fn grep_with(
+        &self,
+        pattern: &str,
+        options: &GrepOptions,
+    ) -> Result<GrepResults, StoreError> {
+        grep_parallel(
+            pattern,
+            options,
+            self.show(".", TraversalOrder::PreOrder)
+                .expect("Root node of internal tree could not be found"),
+            self.search_index.as_ref(),
+        )
+    }
+
+    #[cfg(feature = "parsed-passwords")]
+    pub fn search_fields(
+        &self,
+        query: &crate::parsed::FieldQuery,
+    ) -> Result<crate::parsed::FieldResults, StoreError> {
+        crate::parsed::search_fields(
+            query,
+            self.show(".", TraversalOrder::PreOrder)
+                .expect("Root node of internal tree could not be found"),
+            self.search_index.as_ref(),
+        )
+    }
+
     pub fn has_git(&self) -> bool {
         self.git.is_some()
     }
@@ -246,6 +417,44 @@ impl Store {
         self.git.as_mut()
     }
 
+    pub(crate) fn git_ref(&self) -> Option<&Git> {
+        self.git.as_ref()
+    }
+
+    // like `pass git init`, but the contents and the diff configuration go into a single commit
+    pub fn init_git(&mut self, remote: Option<(&str, &str)>) -> Result<&mut Git, StoreError> {
+        let git = Git::init(
+            &self.path,
+            self.passphrase_provider.clone(),
+            self.credentials_provider.clone(),
+            self.signing_keys.clone(),
+        )
+        .with_store_error("init repository")?;
+        let git = self.git.insert(git);
+
+        let gitattributes = self.path.join(".gitattributes");
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .mode(0o666 & !self.umask)
+            .open(&gitattributes)
+            .with_store_error(gitattributes.display().to_string())?;
+        file.write_all(b"*.gpg diff=gpg\n")
+            .with_store_error(gitattributes.display().to_string())?;
+        drop(file);
+
+        git.add(&[&gitattributes])
+            .with_store_error("failed to add .gitattributes to git")?;
+        git.commit("Add current contents of password store.")
+            .with_store_error("failed to commit initial contents to git")?;
+        if let Some((name, url)) = remote {
+            git.add_remote(name, url)
+                .with_store_error("failed to add git remote")?;
+        }
+
+        Ok(git)
+    }
+
     /// Either a relative path from the store's root or an absolute path where the
     /// password store's location is a prefix of the path.
     ///
@@ -289,7 +498,13 @@ impl Store {
                 .data()
                 .path();
             if path == root_path {
-                return Ok(Entries::new(&self.tree, self.path.clone(), node_id, order));
+                return Ok(Entries::new(
+                    &self.tree,
+                    self.path.clone(),
+                    self.passphrase_provider.clone(),
+                    node_id,
+                    order,
+                ));
             }
         }
 
@@ -323,7 +538,13 @@ impl Store {
         let node_id =
             id.expect("Store entry not found in password store although it must be available!");
 
-        Ok(Entries::new(&self.tree, self.path.clone(), node_id, order))
+        Ok(Entries::new(
+            &self.tree,
+            self.path.clone(),
+            self.passphrase_provider.clone(),
+            node_id,
+            order,
+        ))
     }
 
     pub fn mut_directory(&mut self, directory: Directory) -> MutDirectory {
@@ -353,7 +574,13 @@ impl Store {
             .insert(node, InsertBehavior::UnderNode(parent))
             .expect("Parent of inserted password does not exist in internal tree");
 
-        Password::new(name, path, self.path.clone(), node_id)
+        Password::new(
+            name,
+            path,
+            self.path.clone(),
+            node_id,
+            self.passphrase_provider.clone(),
+        )
     }
 
     fn insert_directory_into_tree(
@@ -409,11 +636,160 @@ impl Store {
         ))
     }
 
+    // adds the missing directories on the way, nodes which already exist are kept
+    fn insert_path_into_tree(&mut self, path: &Path) {
+        let relative = path
+            .strip_prefix(&self.path)
+            .expect("Path is not inside this password store")
+            .to_owned();
+        let mut node_id = self
+            .tree
+            .root_node_id()
+            .expect("Cannot find root node in internal tree")
+            .clone();
+        let mut current = self.path.clone();
+        for component in relative.components() {
+            current.push(component);
+            if Self::is_special_entry(&current) {
+                return;
+            }
+
+            let existing = self
+                .tree
+                .children_ids(&node_id)
+                .expect("Node of restored path does not exist in internal tree")
+                .find(|child| self.tree.get(child).unwrap().data().path() == current)
+                .cloned();
+            node_id = match existing {
+                Some(existing) => existing,
+                None => {
+                    let name = component.as_os_str().to_string_lossy().to_string();
+                    let data = if current != path {
+                        PassNode::Directory {
+                            name,
+                            path: current.clone(),
+                        }
+                    } else if Self::is_password(&current) {
+                        PassNode::Password {
+                            name: current.file_stem().unwrap().to_string_lossy().to_string(),
+                            path: current.clone(),
+                        }
+                    } else {
+                        PassNode::NormalFile {
+                            name,
+                            path: current.clone(),
+                        }
+                    };
+                    self.tree
+                        .insert(Node::new(data), InsertBehavior::UnderNode(&node_id))
+                        .expect("Parent of restored path does not exist in internal tree")
+                }
+            };
+        }
+    }
+
+    /// Restore a password or directory as it was in `commit`, e.g. after it has been removed.
+    ///
+    /// The path is relative to the store's root, passwords are given without the `.gpg`
+    /// extension. Passwords are re-encrypted if the recipients changed since `commit`, files
+    /// which were added to a directory after `commit` are kept.
+    pub fn restore<P: AsRef<Path>>(&mut self, path: P, commit: Oid) -> Result<Entry, StoreError> {
+        let path = path.as_ref();
+        let path = path.strip_prefix(&self.path).unwrap_or(path).to_owned();
+        let restored = restore_files(self, &path, commit)?;
+        for file in &restored.files {
+            self.insert_path_into_tree(file);
+        }
+
+        let id = commit.to_string();
+        let mut message = format!(
+            "Restore '{}' from commit {}.",
+            path.display(),
+            &id[..7.min(id.len())],
+        );
+        if restored.reencrypted > 0 {
+            message = format!(
+                "{}\n\nReencrypt {} password(s) for the current gpg-ids.\n",
+                message, restored.reencrypted,
+            );
+        }
+        if let Some(git) = self.git() {
+            let files = restored.files.iter().map(PathBuf::as_path).collect::<Vec<_>>();
+            git.add(&files)
+                .with_store_error("failed to add restored entry to git")?;
+            git.commit(message)
+                .with_store_error("failed to commit restored entry to git")?;
+        }
+
+        Ok(self
+            .show(&restored.root, TraversalOrder::PreOrder)?
+            .next()
+            .expect("Restored entry not found in internal tree"))
+    }
+
+    /// Apply several changes to the store and record them in a single git commit.
+    ///
+    /// `summary` becomes the subject of the commit, the messages of the individual changes
+    /// follow in its body. If `changes` fail, the working tree is reset to the last commit and
+    /// nothing is committed. As this would discard uncommitted changes, a repository which is
+    /// not clean gets every change committed on its own instead and a
+    /// [`StoreError::BatchNotClean`] is added to the store errors. Without git the changes are
+    /// applied as they happen and cannot be rolled back.
+    pub fn batch<S, T, F>(&mut self, summary: S, changes: F) -> Result<T, StoreError>
+    where
+        S: Into<String>,
+        F: FnOnce(&mut Store) -> Result<T, StoreError>,
+    {
+        let summary = summary.into();
+        let started = match self.git() {
+            Some(git) => match git.begin_batch() {
+                Ok(started) => started,
+                Err(err) if err.code() == ErrorCode::Modified => {
+                    self.errors.push(StoreError::BatchNotClean(summary.clone()));
+                    false
+                }
+                Err(err) => return Err(err).with_store_error("failed to begin batch"),
+            },
+            None => false,
+        };
+        if !started {
+            return changes(self);
+        }
+
+        // node ids stay valid in a clone, so entries obtained before the batch survive a rollback
+        let tree = self.tree.clone();
+        let result = changes(self).and_then(|value| {
+            self.git()
+                .expect("Git repository removed during batch")
+                .commit_batch(&summary)
+                .with_store_error("failed to commit batch to git")?;
+            Ok(value)
+        });
+        let result = result.or_else(|err| {
+            let rollback = self
+                .git()
+                .expect("Git repository removed during batch")
+                .rollback_batch();
+            self.tree = tree;
+            // the failed change is what the caller needs to know about, even if more went wrong
+            Err(match rollback {
+                Ok(()) => err,
+                Err(rollback) => StoreError::BatchRollback(Box::new(err), rollback),
+            })
+        });
+        // records of rolled back passwords no longer match their blob ids and get refreshed
+        self.flush_search_index();
+        result
+    }
+
     pub fn insert_directory(
         &mut self,
         inserter: &DirectoryInserter,
     ) -> Result<Directory, StoreError> {
-        fs::create_dir(&inserter.path).with_store_error(inserter.path.display().to_string())?;
+        DirBuilder::new()
+            .mode(0o777 & !self.umask)
+            .create(&inserter.path)
+            .with_store_error(inserter.path.display().to_string())?;
 
         Ok(self.insert_directory_into_tree(
             inserter.name.clone(),
@@ -425,4 +801,32 @@ impl Store {
     pub fn location(&self) -> &Path {
         &self.path
     }
+
+    pub(crate) fn umask(&self) -> u32 {
+        self.umask
+    }
+
+    pub(crate) fn signing_keys(&self) -> &[String] {
+        &self.signing_keys
+    }
+
+    pub(crate) fn passphrase_provider(&self) -> &PassphraseProvider {
+        &self.passphrase_provider
+    }
+
+    pub(crate) fn search_index(&mut self) -> Option<&mut SearchIndex> {
+        self.search_index
+            .as_mut()
+            .map(|index| index.get_mut().expect("Search index poisoned"))
+    }
+
+    // a running batch writes the index once it is done, a failure is reported but not fatal
+    pub(crate) fn flush_search_index(&mut self) {
+        if self.git_ref().map_or(false, Git::in_batch) {
+            return;
+        }
+        if let Some(Err(err)) = self.search_index().map(SearchIndex::flush) {
+            self.push_error(err);
+        }
+    }
 }
@@ -2,7 +2,7 @@ use std::path::PathBuf;
 
 use id_tree::{LevelOrderTraversalIds, NodeId, PostOrderTraversalIds, PreOrderTraversalIds, Tree};
 
-use crate::{Entry, PassNode};
+use crate::{Entry, PassNode, PassphraseProvider};
 
 pub enum TraversalOrder {
     LevelOrder,
@@ -20,12 +20,14 @@ pub struct Entries<'a> {
     iter: EntriesTraversal<'a>,
     tree: &'a Tree<PassNode>,
     root: PathBuf,
+    passphrase_provider: PassphraseProvider,
 }
 
 impl<'a> Entries<'a> {
     pub(crate) fn new(
         tree: &'a Tree<PassNode>,
         root: PathBuf,
+        passphrase_provider: PassphraseProvider,
         node_id: &'a NodeId,
         order: TraversalOrder,
     ) -> Self {
@@ -44,7 +46,12 @@ impl<'a> Entries<'a> {
             ),
         };
 
-        Self { iter, tree, root }
+        Self {
+            iter,
+            tree,
+            root,
+            passphrase_provider,
+        }
     }
 }
 
@@ -64,6 +71,11 @@ impl<'a> Iterator for Entries<'a> {
             .expect("node id for node which must exists not found")
             .data()
             .clone();
-        Some(Entry::new(node_id, data, self.root.clone()))
+        Some(Entry::new(
+            node_id,
+            data,
+            self.root.clone(),
+            self.passphrase_provider.clone(),
+        ))
     }
 }
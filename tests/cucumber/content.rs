@@ -1,10 +1,17 @@
+use std::os::unix::fs::PermissionsExt;
 use std::panic::AssertUnwindSafe;
 use std::process::{Command, Stdio};
-use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use std::path::{Path, PathBuf};
 
 use cucumber::{then, when};
-use pass::{GitRemote, Store, GpgKeyId, BranchStatus};
+use pass::{CommitSignature, GitProgress, GitRemote, Store, GpgKeyId, BranchStatus};
+use pass::SignaturePolicy;
 use pass::{Traversal, TraversalOrder, PasswordChange, EntryKind};
+use pass::{FindOptions, GrepMatch, GrepOptions, GrepResults, MatchMode, MatchTarget};
+use pass::{Clipboard, ClipboardDuration, MemoryClipboard, Selection, XSelection};
+
+use pass::StoreError;
 
 use crate::world::{IncrementalWorld, ResolvingStoreBuilder};
 use crate::{DIR, PW};
@@ -12,7 +19,12 @@ use crate::{DIR, PW};
 #[then("the password store is empty")]
 fn the_password_store_is_empty(world: &mut IncrementalWorld) {
     if let IncrementalWorld::Successful { store, .. } = world {
-        let length = store.show(".", TraversalOrder::LevelOrder).unwrap().count();
+        // the first entry is always the store's root directory
+        let length = store
+            .show(".", TraversalOrder::LevelOrder)
+            .unwrap()
+            .skip(1)
+            .count();
         if length > 0 {
             panic!(
                 "Store is not empty when it should be! Actual length: {}",
@@ -68,6 +80,74 @@ fn the_password_stores_directory_contains_a_gpg_id_file(world: &mut IncrementalW
     }
 }
 
+#[then("the password store's GPG ID file is signed")]
+fn the_password_stores_gpg_id_file_is_signed(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        let path = store.location().join(".gpg-id.sig");
+        if !path.exists() {
+            panic!(
+                "Store directory does not contain a GPG ID signature! Path: {}",
+                path.display(),
+            );
+        }
+
+        let root = store
+            .show(".", TraversalOrder::PreOrder).expect("Root directory not found")
+            .next().expect("Root directory not found")
+            .directory().expect("Not a directory");
+        root.gpg_ids(store).expect("GPG ID signature could not be verified");
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[when("the store's GPG ID file is edited")]
+fn the_stores_gpg_id_file_is_edited(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        let path = store.location().join(".gpg-id");
+        let mut gpg_ids = std::fs::read_to_string(&path).expect("Could not read GPG ID file");
+        gpg_ids.push_str("test2@key.email\n");
+        std::fs::write(&path, gpg_ids).expect("Could not edit GPG ID file");
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[when("the store's GPG ID signature is removed")]
+fn the_stores_gpg_id_signature_is_removed(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        std::fs::remove_file(store.location().join(".gpg-id.sig"))
+            .expect("Could not remove GPG ID signature");
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[then("the GPG IDs of the store cannot be verified")]
+fn the_gpg_ids_of_the_store_cannot_be_verified(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        let root = store
+            .show(".", TraversalOrder::PreOrder).expect("Root directory not found")
+            .next().expect("Root directory not found")
+            .directory().expect("Not a directory");
+        root.unverified_gpg_ids().expect("Unverified GPG IDs could not be read");
+        assert!(matches!(
+            root.gpg_ids(store),
+            Err(StoreError::InvalidGpgIdSignature(_)),
+        ));
+
+        // encrypting searches the GPG IDs on its own and must not trust them either
+        let inserted = root
+            .password_insertion("Ready Room")
+            .passphrase("what-are-our-options")
+            .insert(store);
+        assert!(matches!(inserted, Err(StoreError::InvalidGpgIdSignature(_))));
+        assert!(!store.location().join("Ready Room.gpg").exists());
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
 #[then("the password store contains passwords")]
 fn the_password_store_contains_passwords(world: &mut IncrementalWorld) {
     if let IncrementalWorld::Successful { store, home, envs } =
@@ -131,6 +211,88 @@ fn the_password_store_contains_passwords(world: &mut IncrementalWorld) {
     }
 }
 
+#[then("the history of the password shows the edit and the previous content")]
+fn the_history_shows_the_edit(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::EditedPassword {
+        store, password, ..
+    } = world
+    {
+        let history = password.history(store).expect("Could not read history");
+        assert_eq!(history.len(), 2, "Password was not changed exactly twice");
+        assert_eq!(
+            history[0].summary(),
+            "Edit password for 'Manufacturers/Sokor' using libpass."
+        );
+        assert_eq!(history[0].author(), "Test User");
+        assert_eq!(history[0].email(), "test@key.email");
+        assert!(history[0].time() >= history[1].time());
+
+        let previous = password
+            .decrypt_at(store, history[1].id())
+            .expect("Could not decrypt previous revision");
+        assert_eq!(
+            previous.lines().collect::<Vec<_>>(),
+            vec!["pum-yIghoSQo'", "Better not tell Picard about this."]
+        );
+    } else {
+        panic!("World state is not EditedPassword!");
+    }
+}
+
+#[then("the history of the password follows the rename")]
+fn the_history_follows_the_rename(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::RenamedPassword {
+        store, password, ..
+    } = world
+    {
+        let history = password.history(store).expect("Could not read history");
+        assert_eq!(history.len(), 2, "Password was not changed exactly twice");
+        assert_eq!(
+            history[0].path(),
+            Path::new("Manufacturers/None of your concern.gpg")
+        );
+        assert_eq!(history[1].path(), Path::new("Manufacturers/Sokor.gpg"));
+
+        let previous = password
+            .decrypt_at(store, history[1].id())
+            .expect("Could not decrypt password before it was renamed");
+        assert_eq!(previous.passphrase(), Some("pum-yIghoSQo'"));
+    } else {
+        panic!("World state is not RenamedPassword!");
+    }
+}
+
+#[then("the history of the password contains the merged remote commit")]
+fn the_history_contains_the_merged_remote_commit(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        let password = store
+            .show("./Manufacturers/Sokor.gpg", TraversalOrder::PreOrder)
+            .expect("Could not find password")
+            .next()
+            .expect("Password not found")
+            .password()
+            .expect("Password is a directory");
+        let history = password.history(store).expect("Could not read history");
+        assert_eq!(history.len(), 2, "Merged remote commit is missing from history");
+        assert_eq!(history[0].author(), "Remote User");
+        assert_eq!(
+            history[0].summary(),
+            "Add given password for Manufacturers/Sokor to store."
+        );
+        password
+            .decrypt_at(store, history[0].id())
+            .expect("Could not decrypt merged revision");
+
+        let unknown = git2::Oid::from_str("0123456789abcdef0123456789abcdef01234567").unwrap();
+        match password.decrypt_at(store, unknown) {
+            Err(StoreError::NotInRevision(..)) => {}
+            other => panic!("Unexpected result for unknown commit: {:?}", other.map(|_| ())),
+        }
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
 #[then("the repository is clean and contains a new commit")]
 fn the_repository_is_clean_and_contains_a_new_commit(world: &mut IncrementalWorld) {
     let envs = match world {
@@ -138,6 +300,7 @@ fn the_repository_is_clean_and_contains_a_new_commit(world: &mut IncrementalWorl
         IncrementalWorld::EditedPassword { envs, .. } => envs,
         IncrementalWorld::RemovedPassword { envs, .. } => envs,
         IncrementalWorld::RenamedPassword { envs, .. } => envs,
+        IncrementalWorld::MovedPassword { envs, .. } => envs,
         IncrementalWorld::NewPasswordAndDirectory { envs, .. } => envs,
         IncrementalWorld::RenamedDirectory { envs, .. } => envs,
         IncrementalWorld::RemovedDirectory { envs, .. } => envs,
@@ -230,6 +393,22 @@ fn the_repository_is_clean_and_contains_a_new_commit(world: &mut IncrementalWorl
 
             assert!(status.success(), "Sokor password has not been renamed!");
         }
+        IncrementalWorld::MovedPassword { envs, .. } => {
+            assert_eq!(stdout.lines().count(), 8, "Not enough commits");
+            assert_eq!(
+                stdout.lines().next().unwrap(),
+                "Move 'Manufacturers/Sokor' to 'Sokor'."
+            );
+
+            let status = Command::new("pass")
+                .args(&["show", "Sokor"])
+                .envs(envs.clone())
+                .stdout(Stdio::piped())
+                .status()
+                .expect("Could not read Sokor password content");
+
+            assert!(status.success(), "Sokor password has not been moved!");
+        }
         IncrementalWorld::NewPasswordAndDirectory { envs, .. } => {
             assert_eq!(stdout.lines().count(), 3, "Not enough commits");
             assert_eq!(
@@ -287,6 +466,77 @@ fn the_repository_is_clean_and_contains_a_new_commit(world: &mut IncrementalWorl
     };
 }
 
+#[when(regex = r#"^git is initialized for the password store( with the remote "(.*)" at "(.*)")?$"#)]
+fn git_is_initialized_for_the_password_store(
+    world: &mut IncrementalWorld,
+    with_remote: String,
+    name: String,
+    url: String,
+) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        let remote = Some((name.as_str(), url.as_str())).filter(|_| !with_remote.is_empty());
+        store
+            .init_git(remote)
+            .expect("Failed to initialize git for the password store");
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[then("the password store has a git repository")]
+fn the_password_store_has_a_git_repository(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        assert!(store.has_git(), "Password store is not using git");
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[then(regex = r#"^the password store has the git remote "(.*)" at "(.*)"$"#)]
+fn the_password_store_has_the_git_remote(world: &mut IncrementalWorld, name: String, url: String) {
+    if let IncrementalWorld::Successful { envs, .. } = world {
+        let output = Command::new("pass")
+            .args(&["git", "remote", "get-url", &name])
+            .envs(envs.clone())
+            .stdout(Stdio::piped())
+            .output()
+            .expect("Could not read git remote");
+        assert!(output.status.success(), "Git remote {} does not exist", name);
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), url);
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[then("the git repository contains the initial commit")]
+fn the_git_repository_contains_the_initial_commit(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Successful { envs, .. } = world {
+        let output = Command::new("pass")
+            .args(&["git", "log", "--pretty=format:%s"])
+            .envs(envs.clone())
+            .stdout(Stdio::piped())
+            .output()
+            .expect("Could not check git commits");
+        let stdout = String::from_utf8(output.stdout).expect("Could not read stdout as UTF-8");
+        assert_eq!(
+            stdout,
+            "Add current contents of password store."
+        );
+
+        let output = Command::new("pass")
+            .args(&["git", "show", "--name-only", "--pretty=format:", "HEAD"])
+            .envs(envs.clone())
+            .stdout(Stdio::piped())
+            .output()
+            .expect("Could not check files of the initial commit");
+        let stdout = String::from_utf8(output.stdout).expect("Could not read stdout as UTF-8");
+        assert!(stdout.lines().any(|file| file == ".gitattributes"), "{}", stdout);
+        assert!(stdout.lines().any(|file| file == ".gpg-id"), "{}", stdout);
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
 #[then("the repository is clean")]
 fn the_repository_is_clean(world: &mut IncrementalWorld) {
     let envs = match world {
@@ -331,6 +581,28 @@ fn pushing_the_commit_succeeds(world: &mut IncrementalWorld) {
     }
 }
 
+#[then("the pushed branch is reported as accepted by the remote")]
+fn the_pushed_branch_is_reported_as_accepted_by_the_remote(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Pushed { progress, .. } = world {
+        let statuses = progress
+            .iter()
+            .filter_map(|event| match event {
+                GitProgress::PushStatus(status) => Some(status),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(statuses.len(), 1, "Not exactly one pushed reference: {:?}", statuses);
+        assert_eq!(statuses[0].reference(), "refs/heads/main");
+        assert!(
+            !statuses[0].is_rejected(),
+            "Push was rejected: {:?}",
+            statuses[0].rejection()
+        );
+    } else {
+        panic!("World state is not Pushed!");
+    }
+}
+
 #[then("pushing the commit fails")]
 fn pushing_the_commit_fails(world: &mut IncrementalWorld) {
     // This is needed to move out of AssertUnwindSafe
@@ -758,27 +1030,224 @@ fn the_remotes_commits_are_merged(world: &mut IncrementalWorld) {
     }
 }
 
-#[when("a password is opened")]
-fn a_password_is_opened(world: &mut IncrementalWorld) {
+fn open_password(world: &mut IncrementalWorld, name: &str) {
     if let IncrementalWorld::Successful { store, .. } = world {
         let entry = store
-            .show("./Manufacturers/StrutCo.gpg", TraversalOrder::PreOrder)
+            .show(&format!("./{}.gpg", name), TraversalOrder::PreOrder)
             .unwrap()
             .next()
-            .expect("Manufacturers/StrutCo password not found in password store!");
+            .unwrap_or_else(|| panic!("{} password not found in password store!", name));
 
-        let strutco = entry
+        let password = entry
             .password()
-            .expect("Manufacturers/StrutCo is not a password but a directory!");
-        let password = strutco
+            .unwrap_or_else(|| panic!("{} is not a password but a directory!", name))
             .decrypt()
-            .expect("Decrypting Manufacturers/StrutCo failed!");
+            .unwrap_or_else(|err| panic!("Decrypting {} failed: {}", name, err));
         *world = IncrementalWorld::DecryptedPassword { password };
     } else {
         panic!("World state is not Successful!");
     }
 }
 
+#[when("a password is opened")]
+fn a_password_is_opened(world: &mut IncrementalWorld) {
+    open_password(world, "Manufacturers/StrutCo");
+}
+
+#[when(regex = r#"^the password "(.*)" is opened$"#)]
+fn the_password_is_opened(world: &mut IncrementalWorld, name: String) {
+    open_password(world, &name);
+}
+
+fn copy_passphrase(world: &mut IncrementalWorld, configure: impl FnOnce(&mut Clipboard)) {
+    // This is needed to move out of AssertUnwindSafe
+    let prev = std::mem::replace(world, IncrementalWorld::Initial);
+
+    if let IncrementalWorld::DecryptedPassword { password } = prev {
+        let memory = MemoryClipboard::new();
+        let mut clipboard = Clipboard::new(memory.clone());
+        configure(&mut clipboard);
+        let copied = Instant::now();
+        let guard = password
+            .copy_passphrase(&clipboard)
+            .expect("Copying the passphrase to the clipboard failed");
+
+        *world = IncrementalWorld::ClipboardPassword {
+            password,
+            clipboard: memory,
+            guard: Some(guard),
+            copied,
+        };
+    } else {
+        panic!("World state is not DecryptedPassword!");
+    }
+}
+
+fn parse_selection(selection: &str) -> Selection {
+    match selection {
+        "clipboard" => Selection::Clipboard,
+        "primary" => Selection::Primary,
+        "secondary" => Selection::Secondary,
+        _ => panic!("Invalid X selection '{}'", selection),
+    }
+}
+
+#[when("the passphrase is copied for an automatically detected duration to the clipboard")]
+fn the_passphrase_is_copied_for_an_automatic_duration(world: &mut IncrementalWorld) {
+    copy_passphrase(world, |clipboard| {
+        clipboard.duration(ClipboardDuration::Automatic);
+    });
+}
+
+#[when(regex = r"^the passphrase is copied for a manually provided duration of (\d+) seconds? to the clipboard$")]
+fn the_passphrase_is_copied_for_a_manual_duration(world: &mut IncrementalWorld, seconds: u64) {
+    copy_passphrase(world, |clipboard| {
+        clipboard.duration(Duration::from_secs(seconds));
+    });
+}
+
+#[when("the passphrase is copied to an automatically detected X selection")]
+fn the_passphrase_is_copied_to_an_automatic_x_selection(world: &mut IncrementalWorld) {
+    copy_passphrase(world, |clipboard| {
+        clipboard.selection(XSelection::Automatic);
+    });
+}
+
+#[when(regex = r"^the passphrase is copied to the (clipboard|primary|secondary) X selection$")]
+fn the_passphrase_is_copied_to_an_x_selection(world: &mut IncrementalWorld, selection: String) {
+    copy_passphrase(world, |clipboard| {
+        clipboard.selection(parse_selection(&selection));
+    });
+}
+
+#[then(regex = r"^the passphrase lasts in the clipboard for (\d+) seconds?$")]
+fn the_passphrase_lasts_in_the_clipboard(world: &mut IncrementalWorld, seconds: u64) {
+    if let IncrementalWorld::ClipboardPassword { password, clipboard, guard, copied } = world {
+        let guard = guard.take().expect("Passphrase was not copied to the clipboard");
+        let selection = guard.selection();
+        let duration = Duration::from_secs(seconds);
+        assert_eq!(guard.duration(), duration);
+
+        // the passphrase must still be there shortly before the timeout expires
+        let almost = (*copied + duration)
+            .checked_duration_since(Instant::now())
+            .and_then(|remaining| remaining.checked_sub(Duration::from_millis(200)))
+            .unwrap_or_default();
+        std::thread::sleep(almost);
+        assert_eq!(clipboard.content(selection).as_deref(), password.passphrase());
+
+        guard.wait().expect("Restoring the clipboard failed");
+        assert!(copied.elapsed() >= duration, "Clipboard was restored too early");
+        assert_eq!(clipboard.content(selection), None);
+    } else {
+        panic!("World state is not ClipboardPassword!");
+    }
+}
+
+#[then(regex = r"^the passphrase is copied to the (clipboard|primary|secondary) X selection$")]
+fn the_passphrase_is_in_the_x_selection(world: &mut IncrementalWorld, selection: String) {
+    if let IncrementalWorld::ClipboardPassword { password, clipboard, guard, .. } = world {
+        let guard = guard.take().expect("Passphrase was not copied to the clipboard");
+        let selection = parse_selection(&selection);
+        assert_eq!(guard.selection(), selection);
+        assert_eq!(clipboard.content(selection).as_deref(), password.passphrase());
+
+        guard.restore().expect("Restoring the clipboard failed");
+        assert_eq!(clipboard.content(selection), None);
+    } else {
+        panic!("World state is not ClipboardPassword!");
+    }
+}
+
+#[cfg(feature = "qr-codes")]
+fn assert_qr_code_renders(qr_code: pass::QrCode) {
+    assert!(!qr_code.to_terminal().is_empty(), "QR code for the terminal is empty");
+    assert!(qr_code.to_svg().contains("<svg"), "QR code is not an SVG image");
+    let png = qr_code.to_png().expect("Could not render QR code as PNG");
+    assert!(png.starts_with(b"\x89PNG"), "QR code is not a PNG image");
+}
+
+#[cfg(feature = "qr-codes")]
+#[then("a QR code can be created for the passphrase")]
+fn a_qr_code_can_be_created_for_the_passphrase(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::DecryptedPassword { password } = world {
+        assert_qr_code_renders(
+            password.passphrase_qr_code().expect("Could not create QR code for passphrase"),
+        );
+    } else {
+        panic!("World state is not DecryptedPassword!");
+    }
+}
+
+#[cfg(all(feature = "qr-codes", feature = "parsed-passwords"))]
+#[then("a QR code can be created for a comment")]
+fn a_qr_code_can_be_created_for_a_comment(world: &mut IncrementalWorld) {
+    // parsing consumes the password, so it is moved out of the world
+    let prev = std::mem::replace(world, IncrementalWorld::Initial);
+
+    if let IncrementalWorld::DecryptedPassword { password } = prev {
+        let password = password.parsed().expect("Could not parse password");
+        assert_qr_code_renders(
+            password.comment_qr_code(0).expect("Could not create QR code for comment"),
+        );
+        assert!(matches!(
+            password.comment_qr_code(1),
+            Err(StoreError::NothingToEncode(..))
+        ));
+    } else {
+        panic!("World state is not DecryptedPassword!");
+    }
+}
+
+#[cfg(all(feature = "qr-codes", feature = "parsed-passwords"))]
+#[then("a QR code can be created for an entry")]
+fn a_qr_code_can_be_created_for_an_entry(world: &mut IncrementalWorld) {
+    // parsing consumes the password, so it is moved out of the world
+    let prev = std::mem::replace(world, IncrementalWorld::Initial);
+
+    if let IncrementalWorld::DecryptedPassword { password } = prev {
+        let password = password.parsed().expect("Could not parse password");
+        assert_qr_code_renders(
+            password.entry_qr_code("username").expect("Could not create QR code for entry"),
+        );
+        assert!(matches!(
+            password.entry_qr_code("password"),
+            Err(StoreError::NothingToEncode(..))
+        ));
+    } else {
+        panic!("World state is not DecryptedPassword!");
+    }
+}
+
+#[cfg(feature = "qr-codes")]
+#[then("a QR code can be created for the complete password")]
+fn a_qr_code_can_be_created_for_the_complete_password(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::DecryptedPassword { password } = world {
+        assert_qr_code_renders(password.qr_code().expect("Could not create QR code for password"));
+    } else {
+        panic!("World state is not DecryptedPassword!");
+    }
+}
+
+#[cfg(feature = "qr-codes")]
+#[then("a QR code can be created for an encrypted password")]
+fn a_qr_code_can_be_created_for_an_encrypted_password(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        let password = store
+            .show("./Manufacturers/StrutCo.gpg", TraversalOrder::PreOrder)
+            .unwrap()
+            .next()
+            .expect("Manufacturers/StrutCo password not found in password store!")
+            .password()
+            .expect("Manufacturers/StrutCo is not a password but a directory!");
+        assert_qr_code_renders(
+            password.qr_code().expect("Could not create QR code for encrypted password"),
+        );
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
 #[when("an existing password is searched in the password store")]
 fn an_existing_password_is_searched_in_the_password_store(world: &mut IncrementalWorld) {
     if let IncrementalWorld::Successful { store, .. } = world {
@@ -805,49 +1274,427 @@ fn a_non_existent_password_is_searched_in_the_password_store(world: &mut Increme
     }
 }
 
-#[when("content of an existing password is searched in the password store")]
-fn content_of_an_existing_password_is_searched_in_the_password_store(world: &mut IncrementalWorld) {
+fn find_options(mode: &str, target: &str, case_sensitive: bool) -> FindOptions {
+    let mut options = FindOptions::new();
+    options
+        .mode(match mode {
+            "exact" => MatchMode::Exact,
+            "substring" => MatchMode::Substring,
+            "glob" => MatchMode::Glob,
+            "regex" => MatchMode::Regex,
+            _ => unreachable!(),
+        })
+        .target(match target {
+            "name" => MatchTarget::Name,
+            "path" => MatchTarget::Path,
+            _ => unreachable!(),
+        })
+        .case_sensitive(case_sensitive);
+    options
+}
+
+#[when(
+    regex = r#"^the password store is (case-sensitively )?searched with the (exact|substring|glob|regex) pattern "(.*)" on the (name|path)$"#
+)]
+fn the_password_store_is_searched_with(
+    world: &mut IncrementalWorld,
+    case_sensitive: String,
+    mode: String,
+    pattern: String,
+    target: String,
+) {
     if let IncrementalWorld::Successful { store, .. } = world {
-        let found_passwords = store
-            .grep("pattern")
-            .map(|password| password.decrypt().expect("could not decrypt password"))
+        let options = find_options(&mode, &target, !case_sensitive.is_empty());
+        let found_entries = store
+            .find_with(&pattern, &options)
+            .expect("Search pattern is invalid")
+            .map(|entry| {
+                entry
+                    .path()
+                    .strip_prefix(store.location())
+                    .expect("Found entry is not in the password store")
+                    .to_owned()
+            })
             .collect::<Vec<_>>();
-        *world = IncrementalWorld::Grep { found_passwords };
+        *world = IncrementalWorld::Search { found_entries };
     } else {
         panic!("World state is not Successful!");
     }
 }
 
-#[when("content of a non-existing password is searched in the password store")]
-fn content_of_a_non_existing_password_is_searched_in_the_password_store(
-    world: &mut IncrementalWorld,
-) {
+#[when(regex = r#"^the password store is searched for "(.*)" without options$"#)]
+fn the_password_store_is_searched_without_options(world: &mut IncrementalWorld, pattern: String) {
     if let IncrementalWorld::Successful { store, .. } = world {
-        let found_passwords = store
-            .grep("romulan cloaking frequency")
-            .map(|password| password.decrypt().expect("could not decrypt password"))
+        let found_entries = store
+            .find(&pattern)
+            .map(|entry| {
+                entry
+                    .path()
+                    .strip_prefix(store.location())
+                    .expect("Found entry is not in the password store")
+                    .to_owned()
+            })
             .collect::<Vec<_>>();
-        *world = IncrementalWorld::Grep { found_passwords };
+        *world = IncrementalWorld::Search { found_entries };
     } else {
         panic!("World state is not Successful!");
     }
 }
 
-#[when(regex = r"^a (\w+ )?password is created$")]
-fn a_new_password_is_created(world: &mut IncrementalWorld) {
-    // This is needed to move out of AssertUnwindSafe
-    let prev = std::mem::replace(world, IncrementalWorld::Initial);
+#[then(regex = r#"^exactly the entries "(.*)" are found$"#)]
+fn exactly_the_entries_are_found(world: &mut IncrementalWorld, entries: String) {
+    if let IncrementalWorld::Search { found_entries } = world {
+        let mut found_entries = found_entries.clone();
+        found_entries.sort();
+        let expected = entries
+            .split(", ")
+            .filter(|entry| !entry.is_empty())
+            .map(PathBuf::from)
+            .collect::<Vec<_>>();
+        assert_eq!(found_entries, expected);
+    } else {
+        panic!("World state is not Search!");
+    }
+}
 
-    if let IncrementalWorld::Successful {
-        mut store,
-        home,
-        envs,
-    } = prev
-    {
-        let root = store
-            .show("./", TraversalOrder::LevelOrder)
-            .expect("could not get root directory of password store")
-            .next()
+#[then(regex = r#"^searching the password store with the regex pattern "(.*)" fails$"#)]
+fn searching_with_an_invalid_regex_fails(world: &mut IncrementalWorld, pattern: String) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        let result = store.find_with(&pattern, &find_options("regex", "path", false));
+        assert!(matches!(result, Err(pass::StoreError::InvalidPattern(..))));
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[when(regex = r#"^the password store is fuzzy searched for "(.*)"$"#)]
+fn the_password_store_is_fuzzy_searched(world: &mut IncrementalWorld, query: String) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        let matches = store.fuzzy_find(&query);
+        *world = IncrementalWorld::FuzzySearch { matches };
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[then(regex = r#"^the best fuzzy match is "(.*)" with all query characters highlighted$"#)]
+fn the_best_fuzzy_match_is(world: &mut IncrementalWorld, name: String) {
+    if let IncrementalWorld::FuzzySearch { matches } = world {
+        let best = matches.first().expect("Fuzzy search found nothing");
+        assert_eq!(best.name(), name);
+        assert_eq!(best.entry().kind(), EntryKind::Password);
+
+        let highlighted = best
+            .positions()
+            .iter()
+            .map(|&position| best.name().chars().nth(position).unwrap())
+            .collect::<String>();
+        assert_eq!(highlighted.to_lowercase(), "mstr");
+        assert!(matches
+            .windows(2)
+            .all(|pair| pair[0].score() >= pair[1].score()));
+    } else {
+        panic!("World state is not FuzzySearch!");
+    }
+}
+
+#[then(regex = r#"^no fuzzy match is found for "(.*)"$"#)]
+fn no_fuzzy_match_is_found_for(world: &mut IncrementalWorld, name: String) {
+    if let IncrementalWorld::FuzzySearch { matches } = world {
+        assert!(matches.iter().all(|found| found.name() != name));
+    } else {
+        panic!("World state is not FuzzySearch!");
+    }
+}
+
+fn grep_store(world: &mut IncrementalWorld, pattern: &str, options: &GrepOptions) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        let results = store
+            .grep_with(pattern, options)
+            .expect("Grep pattern is invalid");
+        *world = IncrementalWorld::ParallelGrep { results };
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[when(regex = r#"^the password store is grepped for the regex "(.*)"( skipping passphrases)?$"#)]
+fn the_password_store_is_grepped_for_the_regex(
+    world: &mut IncrementalWorld,
+    pattern: String,
+    skip_passphrase: String,
+) {
+    grep_store(
+        world,
+        &pattern,
+        GrepOptions::new()
+            .regex(true)
+            .skip_passphrase(!skip_passphrase.is_empty()),
+    );
+}
+
+#[when(
+    regex = r#"^the password store is grepped case-sensitively for "(.*)" on (\d+) threads( skipping passphrases)?$"#
+)]
+fn the_password_store_is_grepped_case_sensitively(
+    world: &mut IncrementalWorld,
+    pattern: String,
+    threads: usize,
+    skip_passphrase: String,
+) {
+    grep_store(
+        world,
+        &pattern,
+        GrepOptions::new()
+            .case_sensitive(true)
+            .skip_passphrase(!skip_passphrase.is_empty())
+            .threads(threads),
+    );
+}
+
+#[when("a password file is corrupted")]
+fn a_password_file_is_corrupted(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        std::fs::write(
+            store.location().join("Manufacturers/StrutCo.gpg"),
+            b"this is not an OpenPGP message",
+        )
+        .expect("Could not corrupt password file");
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+fn single_grep_match<'a>(results: &'a GrepResults, name: &str) -> &'a GrepMatch {
+    assert_eq!(results.matches().len(), 1, "Grep did not find exactly one password");
+    let found = &results.matches()[0];
+    assert!(found.password().path().ends_with(name));
+    found
+}
+
+#[then(regex = r#"^only "(.*)" matches at line (\d+) from byte (\d+) to (\d+)$"#)]
+fn only_one_password_matches_at(
+    world: &mut IncrementalWorld,
+    name: String,
+    position: usize,
+    start: usize,
+    end: usize,
+) {
+    if let IncrementalWorld::ParallelGrep { results } = world {
+        let found = single_grep_match(results, &name);
+        assert_eq!(found.lines().len(), 1);
+        assert_eq!(found.lines()[0].position(), position);
+        assert_eq!(found.lines()[0].ranges(), &[start..end]);
+    } else {
+        panic!("World state is not ParallelGrep!");
+    }
+}
+
+#[then(regex = r#"^only "(.*)" matches on lines (\d+), (\d+) and (\d+)$"#)]
+fn only_one_password_matches_on_lines(
+    world: &mut IncrementalWorld,
+    name: String,
+    first: usize,
+    second: usize,
+    third: usize,
+) {
+    if let IncrementalWorld::ParallelGrep { results } = world {
+        let found = single_grep_match(results, &name);
+        let positions = found
+            .lines()
+            .iter()
+            .map(|line| line.position())
+            .collect::<Vec<_>>();
+        assert_eq!(positions, vec![first, second, third]);
+        assert_eq!(found.lines()[0].ranges(), &[0..3, 3..6]);
+    } else {
+        panic!("World state is not ParallelGrep!");
+    }
+}
+
+#[when(regex = r#"^the line "(.*)" is appended to "(.*)" with this library$"#)]
+fn the_line_is_appended_with_this_library(
+    world: &mut IncrementalWorld,
+    line: String,
+    name: String,
+) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        let password = store
+            .show(&name, TraversalOrder::LevelOrder)
+            .expect("could not find password")
+            .next()
+            .expect("could not find password")
+            .password()
+            .expect("entry is not a password");
+        password
+            .decrypt()
+            .expect("Could not decrypt password")
+            .append_line(store, line)
+            .expect("Failed to append line");
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[then(regex = r#"^"(.*)" matches at line (\d+) as well$"#)]
+fn the_password_matches_as_well(world: &mut IncrementalWorld, name: String, position: usize) {
+    if let IncrementalWorld::ParallelGrep { results } = world {
+        let found = results
+            .matches()
+            .iter()
+            .find(|found| found.password().path().ends_with(&name))
+            .expect("Password does not match");
+        let positions = found
+            .lines()
+            .iter()
+            .map(|line| line.position())
+            .collect::<Vec<_>>();
+        assert_eq!(positions, vec![position]);
+    } else {
+        panic!("World state is not ParallelGrep!");
+    }
+}
+
+#[then("no password content matches")]
+fn no_password_content_matches(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::ParallelGrep { results } = world {
+        assert!(results.matches().is_empty());
+        assert_eq!(results.errors().count(), 0);
+    } else {
+        panic!("World state is not ParallelGrep!");
+    }
+}
+
+#[then("the search index does not contain plain text")]
+fn the_search_index_does_not_contain_plain_text(_world: &mut IncrementalWorld) {
+    let home = PathBuf::from(std::env::var("HOME").expect("HOME is not set"));
+    let index =
+        std::fs::read(home.join("search-index.gpg")).expect("Search index was not written");
+    assert!(!index.is_empty());
+    for needle in &["O--O--5", "laforge", "Phone"] {
+        assert!(!index.windows(needle.len()).any(|window| window == needle.as_bytes()));
+    }
+}
+
+#[then(regex = r#"^the grep reports a decryption error for "(.*)"$"#)]
+fn the_grep_reports_a_decryption_error(world: &mut IncrementalWorld, name: String) {
+    if let IncrementalWorld::ParallelGrep { results } = world {
+        let errors = results.errors().collect::<Vec<_>>();
+        assert_eq!(errors.len(), 1);
+        match errors[0] {
+            pass::StoreError::Grep(path, _) => assert!(path.ends_with(&name)),
+            err => panic!("Unexpected grep error: {:?}", err),
+        }
+    } else {
+        panic!("World state is not ParallelGrep!");
+    }
+}
+
+#[cfg(feature = "parsed-passwords")]
+#[when(
+    regex = r#"^the password entries are (case-sensitively )?searched for "(.*)" with the (exact|substring|glob|regex) value "(.*)"$"#
+)]
+fn the_password_entries_are_searched(
+    world: &mut IncrementalWorld,
+    case_sensitive: String,
+    key: String,
+    mode: String,
+    pattern: String,
+) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        let mode = match mode.as_str() {
+            "exact" => MatchMode::Exact,
+            "substring" => MatchMode::Substring,
+            "glob" => MatchMode::Glob,
+            "regex" => MatchMode::Regex,
+            _ => unreachable!(),
+        };
+        let results = store
+            .search_fields(
+                pass::parsed::FieldQuery::new(key, pattern)
+                    .mode(mode)
+                    .case_sensitive(!case_sensitive.is_empty()),
+            )
+            .expect("Field query is invalid");
+        assert_eq!(results.errors().count(), 0);
+        *world = IncrementalWorld::FieldSearch { results };
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[cfg(feature = "parsed-passwords")]
+#[then(regex = r#"^only "(.*)" has the entry "(.*)" with value "(.*)" at line (\d+)$"#)]
+fn only_one_password_has_the_entry(
+    world: &mut IncrementalWorld,
+    name: String,
+    key: String,
+    value: String,
+    position: usize,
+) {
+    if let IncrementalWorld::FieldSearch { results } = world {
+        assert_eq!(results.matches().len(), 1, "Not exactly one password was found");
+        let found = &results.matches()[0];
+        assert!(found.password().path().ends_with(&name));
+        assert_eq!(found.entries(), &[(position, key, value)]);
+    } else {
+        panic!("World state is not FieldSearch!");
+    }
+}
+
+#[cfg(feature = "parsed-passwords")]
+#[then("no password entries are found")]
+fn no_password_entries_are_found(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::FieldSearch { results } = world {
+        assert!(results.matches().is_empty());
+    } else {
+        panic!("World state is not FieldSearch!");
+    }
+}
+
+#[when("content of an existing password is searched in the password store")]
+fn content_of_an_existing_password_is_searched_in_the_password_store(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        let found_passwords = store
+            .grep("pattern")
+            .map(|password| password.decrypt().expect("could not decrypt password"))
+            .collect::<Vec<_>>();
+        *world = IncrementalWorld::Grep { found_passwords };
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[when("content of a non-existing password is searched in the password store")]
+fn content_of_a_non_existing_password_is_searched_in_the_password_store(
+    world: &mut IncrementalWorld,
+) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        let found_passwords = store
+            .grep("romulan cloaking frequency")
+            .map(|password| password.decrypt().expect("could not decrypt password"))
+            .collect::<Vec<_>>();
+        *world = IncrementalWorld::Grep { found_passwords };
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[when(regex = r"^a (\w+ )?password is created$")]
+fn a_new_password_is_created(world: &mut IncrementalWorld) {
+    // This is needed to move out of AssertUnwindSafe
+    let prev = std::mem::replace(world, IncrementalWorld::Initial);
+
+    if let IncrementalWorld::Successful {
+        mut store,
+        home,
+        envs,
+    } = prev
+    {
+        let root = store
+            .show("./", TraversalOrder::LevelOrder)
+            .expect("could not get root directory of password store")
+            .next()
             .expect("could not get root directory of password store")
             .directory()
             .expect("Root directory is not a directory");
@@ -974,11 +1821,8 @@ fn a_password_is_renamed(world: &mut IncrementalWorld) {
     }
 }
 
-#[when("the binary file is edited")]
-fn the_binary_file_is_edited(world: &mut IncrementalWorld) {
-    use std::fs::File;
-    use std::io::Write;
-
+#[when("a password is moved")]
+fn a_password_is_moved(world: &mut IncrementalWorld) {
     // This is needed to move out of AssertUnwindSafe
     let prev = std::mem::replace(world, IncrementalWorld::Initial);
 
@@ -988,30 +1832,115 @@ fn the_binary_file_is_edited(world: &mut IncrementalWorld) {
         envs,
     } = prev
     {
-        let content = &[0xFE, 0xED, 0xC0, 0xDE];
-        let binary_file_path = store.location().join("Manufacturers/Sokor-Starmap");
-        let mut binary_file = File::create(&binary_file_path).expect("Failed to create binary file");
-        binary_file.write_all(content).expect("Failed to write to binary file");
+        let root = store
+            .show("./", TraversalOrder::LevelOrder)
+            .expect("could not get root directory of password store")
+            .next()
+            .expect("could not get root directory of password store")
+            .directory()
+            .expect("Root directory is not a directory");
+        let mut password = store
+            .show("Manufacturers/Sokor", TraversalOrder::LevelOrder)
+            .expect("could not find Sokor password")
+            .next()
+            .expect("could not find Sokor password")
+            .password()
+            .expect("Sokor is not a password")
+            .make_mut(&mut store);
+        password
+            .move_to(&root)
+            .expect("Could not move password");
+        let password = password.make_immut();
 
-        let git = store.git().expect("Store not using git");
-        git.add(&[&binary_file_path]).expect("Failed to add binary file to git");
-        git.commit("Add 'Manufacturers/Sokor-Starmap' to store").expect("Failed to commit binary file to git");
+        *world = IncrementalWorld::MovedPassword {
+            store,
+            home,
+            envs,
+            password,
+        };
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
 
-        *world = IncrementalWorld::Successful {
+#[when("a password is copied")]
+fn a_password_is_copied(world: &mut IncrementalWorld) {
+    // This is needed to move out of AssertUnwindSafe
+    let prev = std::mem::replace(world, IncrementalWorld::Initial);
+
+    if let IncrementalWorld::Successful {
+        mut store,
+        home,
+        envs,
+    } = prev
+    {
+        let root = store
+            .show("./", TraversalOrder::LevelOrder)
+            .expect("could not get root directory of password store")
+            .next()
+            .expect("could not get root directory of password store")
+            .directory()
+            .expect("Root directory is not a directory");
+        let mut password = store
+            .show("Manufacturers/Sokor", TraversalOrder::LevelOrder)
+            .expect("could not find Sokor password")
+            .next()
+            .expect("could not find Sokor password")
+            .password()
+            .expect("Sokor is not a password")
+            .make_mut(&mut store);
+        password
+            .copy_to(&root)
+            .expect("Could not copy password");
+        let password = password.make_immut();
+
+        *world = IncrementalWorld::CopiedPassword {
             store,
             home,
             envs,
+            password,
         };
     } else {
         panic!("World state is not Successful!");
     }
 }
 
-#[when("the text file is edited")]
-fn the_text_file_is_edited(world: &mut IncrementalWorld) {
-    use std::fs::File;
-    use std::io::Write;
+#[then("a new password exists")]
+fn a_new_password_exists(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::CopiedPassword {
+        store, password, ..
+    } = world
+    {
+        let copy = store
+            .show("Sokor", TraversalOrder::LevelOrder)
+            .expect("could not find copied Sokor password")
+            .next()
+            .expect("could not find copied Sokor password")
+            .password()
+            .expect("Copied Sokor is not a password");
+
+        let original_lines = password
+            .decrypt()
+            .expect("Could not decrypt original Sokor password")
+            .lines()
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        let copied_lines = copy
+            .decrypt()
+            .expect("Could not decrypt copied Sokor password")
+            .lines()
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        assert_eq!(original_lines, copied_lines);
+        assert_ne!(password.path(), copy.path());
+    } else {
+        panic!("World state is not CopiedPassword!");
+    }
+}
 
+#[cfg(feature = "parsed-passwords")]
+#[when("a password with a comment and an entry is created")]
+fn a_parsed_password_is_created(world: &mut IncrementalWorld) {
     // This is needed to move out of AssertUnwindSafe
     let prev = std::mem::replace(world, IncrementalWorld::Initial);
 
@@ -1021,179 +1950,777 @@ fn the_text_file_is_edited(world: &mut IncrementalWorld) {
         envs,
     } = prev
     {
-        let content = "reboot even if system utterly broken";
-        let text_file_path = store.location().join("Manufacturers/Sokor-Greeting");
-        let mut text_file = File::create(&text_file_path).expect("Failed to create text file");
-        text_file.write_all(content.as_bytes()).expect("Failed to write to text file");
+        let mut root = store
+            .show("./", TraversalOrder::LevelOrder)
+            .expect("could not get root directory of password store")
+            .next()
+            .expect("could not get root directory of password store")
+            .directory()
+            .expect("Root directory is not a directory");
 
-        let git = store.git().expect("Store not using git");
-        git.add(&[&text_file_path]).expect("Failed to add text file to git");
-        git.commit("Add 'Manufacturers/Sokor-Greeting' to store").expect("Failed to commit text file to git");
+        let password = root
+            .parsed_password_insertion("Bridge")
+            .passphrase("make-it-so")
+            .comment("Captain's chair")
+            .entry("user", "picard")
+            .insert(&mut store)
+            .expect("Password insertion failed");
+
+        *world = IncrementalWorld::NewPassword {
+            store,
+            home,
+            envs,
+            password,
+        };
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[cfg(feature = "parsed-passwords")]
+#[then(regex = r#"^the new password is stored as "(.*)" with its comment and entry$"#)]
+fn the_new_password_is_stored_as(world: &mut IncrementalWorld, file_name: String) {
+    if let IncrementalWorld::NewPassword { password, .. } = world {
+        assert!(password.path().ends_with(&file_name), "{:?}", password.path());
+        assert!(password.path().is_file(), "{} was not written", file_name);
+
+        let password = password
+            .decrypt()
+            .expect("Decrypting new password failed")
+            .parsed()
+            .expect("Parsing new password failed");
+        assert_eq!(password.passphrase(), Some("make-it-so"));
+        assert_eq!(password.comments().next().map(|(_, c)| c), Some("Captain's chair"));
+        assert_eq!(password.entry("user").map(|(_, v)| v), Some("picard"));
+    } else {
+        panic!("World state is not NewPassword!");
+    }
+}
+
+#[then(regex = r"^the new password respects umask (\d+)$")]
+fn the_new_password_respects_umask(world: &mut IncrementalWorld, umask: String) {
+    if let IncrementalWorld::NewPassword { password, .. } = world {
+        let umask = u32::from_str_radix(&umask, 8).expect("umask is not an octal number");
+        let mode = std::fs::metadata(password.path())
+            .expect("could not read metadata of new password")
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o666 & !umask);
+    } else {
+        panic!("World state is not NewPassword!");
+    }
+}
+
+#[when("the binary file is edited")]
+fn the_binary_file_is_edited(world: &mut IncrementalWorld) {
+    use std::fs::File;
+    use std::io::Write;
+
+    // This is needed to move out of AssertUnwindSafe
+    let prev = std::mem::replace(world, IncrementalWorld::Initial);
+
+    if let IncrementalWorld::Successful {
+        mut store,
+        home,
+        envs,
+    } = prev
+    {
+        let content = &[0xFE, 0xED, 0xC0, 0xDE];
+        let binary_file_path = store.location().join("Manufacturers/Sokor-Starmap");
+        let mut binary_file = File::create(&binary_file_path).expect("Failed to create binary file");
+        binary_file.write_all(content).expect("Failed to write to binary file");
+
+        let git = store.git().expect("Store not using git");
+        git.add(&[&binary_file_path]).expect("Failed to add binary file to git");
+        git.commit("Add 'Manufacturers/Sokor-Starmap' to store").expect("Failed to commit binary file to git");
+
+        *world = IncrementalWorld::Successful {
+            store,
+            home,
+            envs,
+        };
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[when("the text file is edited")]
+fn the_text_file_is_edited(world: &mut IncrementalWorld) {
+    use std::fs::File;
+    use std::io::Write;
+
+    // This is needed to move out of AssertUnwindSafe
+    let prev = std::mem::replace(world, IncrementalWorld::Initial);
+
+    if let IncrementalWorld::Successful {
+        mut store,
+        home,
+        envs,
+    } = prev
+    {
+        let content = "reboot even if system utterly broken";
+        let text_file_path = store.location().join("Manufacturers/Sokor-Greeting");
+        let mut text_file = File::create(&text_file_path).expect("Failed to create text file");
+        text_file.write_all(content.as_bytes()).expect("Failed to write to text file");
+
+        let git = store.git().expect("Store not using git");
+        git.add(&[&text_file_path]).expect("Failed to add text file to git");
+        git.commit("Add 'Manufacturers/Sokor-Greeting' to store").expect("Failed to commit text file to git");
+
+        *world = IncrementalWorld::Successful {
+            store,
+            home,
+            envs,
+        };
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[when("the gpg-id of the store is edited")]
+fn the_gpg_id_of_the_store_is_edited(world: &mut IncrementalWorld) {
+    // This is needed to move out of AssertUnwindSafe
+    let prev = std::mem::replace(world, IncrementalWorld::Initial);
+
+    if let IncrementalWorld::Successful {
+        mut store,
+        home,
+        envs,
+    } = prev
+    {
+        let root = store
+            .show(".", TraversalOrder::PreOrder).expect("Root directory not found")
+            .next().expect("Root directory not found")
+            .directory().expect("Not a directory");
+
+        root.make_mut(&mut store)
+            .add_gpg_id(
+                GpgKeyId::new("test3@key.email")
+                    .expect("GPG key id test@key.email does not exist")
+            ).expect("Could not add new gpg-id");
+
+        *world = IncrementalWorld::Successful {
+            store,
+            home,
+            envs,
+        };
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[when("a directory is created")]
+fn a_directory_is_created(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Successful { ref mut store, .. } = world {
+        let root = store
+            .show(".", TraversalOrder::LevelOrder)
+            .expect("could not find root directory")
+            .next()
+            .expect("could not find root directory")
+            .directory()
+            .expect("Root is not a directory");
+
+        root.directory_insertion("Warp Nacelles")
+            .insert(store)
+            .expect("Could not create Wrap Nacelles directory");
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[when("a password is created in the new directory")]
+fn a_password_is_created_in_the_new_directory(world: &mut IncrementalWorld) {
+    // This is needed to move out of AssertUnwindSafe
+    let prev = std::mem::replace(world, IncrementalWorld::Initial);
+
+    if let IncrementalWorld::Successful {
+        mut store,
+        home,
+        envs,
+    } = prev
+    {
+        let warp_nacelles = store
+            .show("./Warp Nacelles", TraversalOrder::LevelOrder)
+            .expect("could not get root directory of password store")
+            .next()
+            .expect("could not get root directory of password store")
+            .directory()
+            .expect("Root directory is not a directory");
+
+        let password = warp_nacelles
+            .password_insertion("Starfleet")
+            .passphrase("two-nacelles-ftw")
+            .insert(&mut store)
+            .expect("Password insertion failed");
+
+        *world = IncrementalWorld::NewPasswordAndDirectory {
+            store,
+            home,
+            envs,
+            password,
+        };
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[when("a directory is renamed")]
+fn a_directory_is_renamed(world: &mut IncrementalWorld) {
+    // This is needed to move out of AssertUnwindSafe
+    let prev = std::mem::replace(world, IncrementalWorld::Initial);
+
+    if let IncrementalWorld::Successful {
+        mut store,
+        home,
+        envs,
+    } = prev
+    {
+        let mut holo_deck = store
+            .show("Entertainment/Holo Deck", TraversalOrder::LevelOrder)
+            .expect("could not find Entertainment/Holo Deck directory")
+            .next()
+            .expect("could not find Entertainment/Holo Deck directory")
+            .directory()
+            .expect("Holo Deck is not a directory")
+            .make_mut(&mut store);
+        holo_deck
+            .rename("Novels")
+            .expect("Could not rename directory");
+        let directory = holo_deck.make_immut();
+
+        *world = IncrementalWorld::RenamedDirectory {
+            store,
+            home,
+            envs,
+            directory,
+        };
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[when("a directory is removed")]
+fn a_directory_is_removed(world: &mut IncrementalWorld) {
+    // This is needed to move out of AssertUnwindSafe
+    let prev = std::mem::replace(world, IncrementalWorld::Initial);
+
+    if let IncrementalWorld::Successful {
+        mut store,
+        home,
+        envs,
+    } = prev
+    {
+        let entertainment = store
+            .show("Entertainment", TraversalOrder::LevelOrder)
+            .expect("could not find Entertainment directory")
+            .next()
+            .expect("could not find Entertainment directory")
+            .directory()
+            .expect("Entertainment is not a directory")
+            .make_mut(&mut store);
+        let path = entertainment.path().to_owned();
+        entertainment
+            .remove(Traversal::Recursive)
+            .expect("Could not remove directory");
+
+        *world = IncrementalWorld::RemovedDirectory {
+            store,
+            home,
+            envs,
+            path,
+        };
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+fn commit_before(store: &Store, summary: &str) -> git2::Oid {
+    let repo = git2::Repository::open(store.location()).expect("Could not open repository");
+    let mut revwalk = repo.revwalk().expect("Could not walk repository");
+    revwalk.push_head().expect("Repository has no HEAD");
+    for id in revwalk {
+        let commit = repo
+            .find_commit(id.expect("Could not walk repository"))
+            .expect("Commit not found");
+        if commit.summary().map_or(false, |s| s.starts_with(summary)) {
+            return commit.parent_id(0).expect("Commit has no parent");
+        }
+    }
+
+    panic!("No commit starting with \"{}\" found", summary);
+}
+
+#[when("the gpg-ids of the store are extended")]
+fn the_gpg_ids_of_the_store_are_extended(world: &mut IncrementalWorld) {
+    let store = match world {
+        IncrementalWorld::RemovedPassword { store, .. } => store,
+        IncrementalWorld::RemovedDirectory { store, .. } => store,
+        _ => panic!("World state is invalid!"),
+    };
+    let root = store
+        .show(".", TraversalOrder::PreOrder)
+        .expect("Root directory not found")
+        .next()
+        .expect("Root directory not found")
+        .directory()
+        .expect("Not a directory");
+    root.make_mut(store)
+        .add_gpg_id(
+            GpgKeyId::new("test3@key.email").expect("GPG key id test3@key.email does not exist"),
+        )
+        .expect("Could not add new gpg-id");
+}
+
+#[when(regex = "^a GPG ID is added to the store(, which fails)?$")]
+fn a_gpg_id_is_added_to_the_store(world: &mut IncrementalWorld, fails: String) {
+    let store = match world {
+        IncrementalWorld::Successful { store, .. } => store,
+        _ => panic!("World state is invalid!"),
+    };
+    let root = store
+        .show(".", TraversalOrder::PreOrder)
+        .expect("Root directory not found")
+        .next()
+        .expect("Root directory not found")
+        .directory()
+        .expect("Not a directory");
+
+    let result = root.make_mut(store).add_gpg_id(
+        GpgKeyId::new("test3@key.email").expect("GPG key id test3@key.email does not exist"),
+    );
+    assert_eq!(result.is_err(), !fails.is_empty(), "Unexpected result: {:?}", result);
+}
+
+#[when(regex = r#"^the password "(.*)" can be replaced again$"#)]
+fn the_password_can_be_replaced_again(world: &mut IncrementalWorld, name: String) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        let status = Command::new("chattr")
+            .arg("-i")
+            .arg(store.location().join(format!("{}.gpg", name)))
+            .status()
+            .expect("Could not run chattr");
+        assert!(status.success(), "Could not make the password mutable again");
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[when("unsigned commits are rejected")]
+fn unsigned_commits_are_rejected(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        store
+            .git()
+            .expect("store is not using git")
+            .set_signature_policy(SignaturePolicy::RejectUnsigned);
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[when(regex = "^passwords are changed in a batch( which fails)?$")]
+fn passwords_are_changed_in_a_batch(world: &mut IncrementalWorld, fails: String) {
+    let store = match world {
+        IncrementalWorld::Successful { store, .. } => store,
+        _ => panic!("World state is invalid!"),
+    };
+
+    let result = store.batch("Rearrange passwords of the bridge.", |store| {
+        let root = store
+            .show(".", TraversalOrder::LevelOrder)?
+            .next()
+            .expect("could not get root directory of password store")
+            .directory()
+            .expect("Root directory is not a directory");
+        root.password_insertion("Ready Room")
+            .passphrase("picard-delta-5")
+            .insert(store)?;
+
+        let sokor = store
+            .show("Manufacturers/Sokor", TraversalOrder::LevelOrder)?
+            .next()
+            .expect("could not find Sokor password")
+            .password()
+            .expect("Sokor is not a password");
+        sokor.make_mut(store).remove()?;
+
+        if !fails.is_empty() {
+            store
+                .show("Holodeck", TraversalOrder::LevelOrder)?
+                .next()
+                .ok_or_else(|| StoreError::NotInStore("Holodeck".into()))?;
+        }
+
+        Ok(())
+    });
+    assert_eq!(result.is_err(), !fails.is_empty(), "Unexpected batch result: {:?}", result);
+}
+
+#[when(regex = r#"^"(.*)" is restored from before "(.*)"$"#)]
+fn the_entry_is_restored(world: &mut IncrementalWorld, name: String, summary: String) {
+    // This is needed to move out of AssertUnwindSafe
+    let prev = std::mem::replace(world, IncrementalWorld::Initial);
+
+    let (home, mut store, envs) = match prev {
+        IncrementalWorld::EditedPassword { home, store, envs, .. } => (home, store, envs),
+        IncrementalWorld::RemovedPassword { home, store, envs, .. } => (home, store, envs),
+        IncrementalWorld::RemovedDirectory { home, store, envs, .. } => (home, store, envs),
+        _ => panic!("World state is invalid!"),
+    };
+    let commit = commit_before(&store, &summary);
+    let entry = store.restore(&name, commit).expect("Could not restore entry");
+
+    *world = IncrementalWorld::RestoredEntry {
+        home,
+        store,
+        entry,
+        envs,
+    };
+}
+
+#[when(regex = r#"^restoring "(.*)" from before "(.*)" fails$"#)]
+fn restoring_the_entry_fails(world: &mut IncrementalWorld, name: String, summary: String) {
+    // This is needed to move out of AssertUnwindSafe
+    let prev = std::mem::replace(world, IncrementalWorld::Initial);
+
+    if let IncrementalWorld::EditedPassword { home, mut store, envs, .. } = prev {
+        let commit = commit_before(&store, &summary);
+        let result = store.restore(&name, commit);
+        assert!(result.is_err(), "Restoring {} did not fail", name);
+
+        *world = IncrementalWorld::Successful { home, store, envs };
+    } else {
+        panic!("World state is not EditedPassword!");
+    }
+}
+
+#[then(regex = r#"^the password "(.*)" still contains the line "(.*)"$"#)]
+fn the_password_still_contains_the_line(world: &mut IncrementalWorld, name: String, line: String) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        let password = store
+            .show(&format!("./{}.gpg", name), TraversalOrder::PreOrder)
+            .unwrap()
+            .next()
+            .expect("Password not found in password store!")
+            .password()
+            .expect("Password is a directory!")
+            .decrypt()
+            .expect("Could not decrypt password");
+        assert!(
+            password.lines().any(|l| l == line),
+            "{} lost the line '{}'",
+            name,
+            line
+        );
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[then("the GPG IDs of the store are unchanged")]
+fn the_gpg_ids_of_the_store_are_unchanged(world: &mut IncrementalWorld) {
+    let store = match world {
+        IncrementalWorld::Successful { store, .. } => store,
+        _ => panic!("World state is invalid!"),
+    };
+
+    let gpg_id = std::fs::read_to_string(store.location().join(".gpg-id"))
+        .expect("Could not read .gpg-id");
+    assert!(!gpg_id.contains("test3@key.email"), "GPG IDs were changed: {}", gpg_id);
+}
+
+#[then(regex = r#"^the password "(.*)" is encrypted for (\d+) recipients?$"#)]
+fn the_password_is_encrypted_for(world: &mut IncrementalWorld, name: String, recipients: usize) {
+    let store = match world {
+        IncrementalWorld::Successful { store, .. }
+        | IncrementalWorld::MovedPassword { store, .. }
+        | IncrementalWorld::CopiedPassword { store, .. } => store,
+        _ => panic!("World state is invalid!"),
+    };
+
+    let encrypted = std::fs::read(store.location().join(format!("{}.gpg", name)))
+        .expect("Could not read password");
+    let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)
+        .expect("Could not create OpenPGP context");
+    let mut content = Vec::new();
+    let result = ctx
+        .decrypt(&encrypted, &mut content)
+        .expect("Could not decrypt password");
+    assert_eq!(result.recipients().count(), recipients);
+}
+
+#[then("no temporary files are left in the store")]
+fn no_temporary_files_are_left_in_the_store(world: &mut IncrementalWorld) {
+    let store = match world {
+        IncrementalWorld::Successful { store, .. }
+        | IncrementalWorld::MovedPassword { store, .. }
+        | IncrementalWorld::CopiedPassword { store, .. } => store,
+        _ => panic!("World state is invalid!"),
+    };
+
+    for entry in walkdir::WalkDir::new(store.location()) {
+        let entry = entry.expect("Could not walk the store");
+        let name = entry.file_name().to_string_lossy();
+        assert!(!name.starts_with(".tmp"), "Temporary file left: {}", entry.path().display());
+    }
+}
+
+#[then(regex = r#"^the last commit is signed by "(.*)"$"#)]
+fn the_last_commit_is_signed_by(world: &mut IncrementalWorld, key: String) {
+    let store = match world {
+        IncrementalWorld::Successful { store, .. } => store,
+        IncrementalWorld::EditedPassword { store, .. } => store,
+        _ => panic!("World state is invalid!"),
+    };
+
+    let head = git2::Oid::from_str(git_output(store, &["rev-parse", "HEAD"]).trim())
+        .expect("Could not find last commit");
+    let verified = store
+        .git()
+        .expect("store is not using git")
+        .verify_commit(head)
+        .expect("Could not verify last commit");
+    let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)
+        .expect("Could not create OpenPGP context");
+    let expected = ctx.get_key(key).expect("Signing key not found");
+    match verified.signature() {
+        CommitSignature::Good { fingerprint, .. } => {
+            assert_eq!(Ok(fingerprint.as_str()), expected.fingerprint());
+        }
+        signature => panic!("Last commit is not signed properly: {:?}", signature),
+    }
+}
+
+#[then("pulling the unsigned changes from the remote fails")]
+fn pulling_the_unsigned_changes_from_the_remote_fails(world: &mut IncrementalWorld) {
+    let store = match world {
+        IncrementalWorld::Successful { store, .. } => store,
+        _ => panic!("World state is invalid!"),
+    };
+    let head = git_output(store, &["rev-parse", "HEAD"]);
+
+    match store.git().expect("store is not using git").pull() {
+        Ok(_) => panic!("Unsigned changes were pulled"),
+        Err(err) => assert!(
+            err.message().contains("is not signed"),
+            "Pull failed for another reason: {}",
+            err
+        ),
+    }
+    assert_eq!(git_output(store, &["rev-parse", "HEAD"]), head, "Unsigned changes were merged");
+}
+
+fn git_output(store: &Store, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(store.location())
+        .stdout(Stdio::piped())
+        .output()
+        .expect("Could not run git");
+    String::from_utf8(output.stdout).expect("Could not read stdout as UTF-8")
+}
+
+#[then("the batch is committed at once")]
+fn the_batch_is_committed_at_once(world: &mut IncrementalWorld) {
+    let store = match world {
+        IncrementalWorld::Successful { store, .. } => store,
+        _ => panic!("World state is invalid!"),
+    };
+
+    assert_eq!(git_output(store, &["status", "--porcelain"]), "", "Repository not clean");
+    let message = git_output(store, &["log", "-1", "--pretty=format:%B"]);
+    assert_eq!(message.lines().next(), Some("Rearrange passwords of the bridge."));
+    assert!(
+        message.contains("Add password for 'Ready Room' using libpass."),
+        "Insertion missing in batch commit: {}",
+        message
+    );
+    assert!(
+        message.contains("Remove 'Manufacturers/Sokor' from store."),
+        "Removal missing in batch commit: {}",
+        message
+    );
+    let summaries = git_output(store, &["log", "--pretty=format:%s"]);
+    assert!(
+        summaries.lines().all(|summary| {
+            summary != "Add password for 'Ready Room' using libpass."
+                && summary != "Remove 'Manufacturers/Sokor' from store."
+        }),
+        "Changes of the batch were committed separately: {}",
+        summaries
+    );
+}
+
+#[then("the batch is rolled back")]
+fn the_batch_is_rolled_back(world: &mut IncrementalWorld) {
+    let store = match world {
+        IncrementalWorld::Successful { store, .. } => store,
+        _ => panic!("World state is invalid!"),
+    };
+
+    assert_eq!(git_output(store, &["status", "--porcelain"]), "", "Repository not clean");
+    assert_ne!(
+        git_output(store, &["log", "-1", "--pretty=format:%s"]),
+        "Rearrange passwords of the bridge.",
+        "Failed batch was committed"
+    );
+    assert!(
+        !store.location().join("Ready Room.gpg").exists(),
+        "Inserted password was not rolled back"
+    );
+    assert!(
+        store.location().join("Manufacturers/Sokor.gpg").exists(),
+        "Removed password was not rolled back"
+    );
+    store
+        .show("Manufacturers/Sokor", TraversalOrder::LevelOrder)
+        .expect("could not find Sokor password")
+        .next()
+        .expect("Sokor is missing in the store after the rollback");
+}
+
+#[then("the gpg-ids of the store are changed in a single commit")]
+fn the_gpg_ids_of_the_store_are_changed_in_a_single_commit(world: &mut IncrementalWorld) {
+    let store = match world {
+        IncrementalWorld::Successful { store, .. } => store,
+        _ => panic!("World state is invalid!"),
+    };
 
-        *world = IncrementalWorld::Successful {
-            store,
-            home,
-            envs,
-        };
+    assert_eq!(git_output(store, &["status", "--porcelain"]), "", "Repository not clean");
+    let summaries = git_output(store, &["log", "-2", "--pretty=format:%s"]);
+    let mut summaries = summaries.lines();
+    assert!(
+        summaries
+            .next()
+            .unwrap_or_default()
+            .starts_with("Reencrypt store as gpg-ids changed to"),
+        "Reencryption was not committed as a batch"
+    );
+    assert!(
+        !summaries.next().unwrap_or_default().starts_with("Reencrypt"),
+        "Passwords were reencrypted in separate commits"
+    );
+}
+
+#[when("an untracked file is added to the store")]
+fn an_untracked_file_is_added_to_the_store(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        std::fs::write(store.location().join("notes.txt"), "Ask Data about the warp core\n")
+            .expect("Could not write untracked file");
     } else {
         panic!("World state is not Successful!");
     }
 }
 
-#[when("the gpg-id of the store is edited")]
-fn the_gpg_id_of_the_store_is_edited(world: &mut IncrementalWorld) {
-    // This is needed to move out of AssertUnwindSafe
-    let prev = std::mem::replace(world, IncrementalWorld::Initial);
-
-    if let IncrementalWorld::Successful {
-        mut store,
-        home,
-        envs,
-    } = prev
-    {
-        let root = store
-            .show(".", TraversalOrder::PreOrder).expect("Root directory not found")
-            .next().expect("Root directory not found")
-            .directory().expect("Not a directory");
-
-        root.make_mut(&mut store)
-            .add_gpg_id(
-                GpgKeyId::new("test3@key.email")
-                    .expect("GPG key id test@key.email does not exist")
-            ).expect("Could not add new gpg-id");
-
-        *world = IncrementalWorld::Successful {
-            store,
-            home,
-            envs,
-        };
+#[then("the untracked file is still in the store")]
+fn the_untracked_file_is_still_in_the_store(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        assert_eq!(
+            std::fs::read_to_string(store.location().join("notes.txt"))
+                .expect("Untracked file was removed"),
+            "Ask Data about the warp core\n",
+        );
+        assert_eq!(git_output(store, &["status", "--porcelain"]), "?? notes.txt\n");
     } else {
         panic!("World state is not Successful!");
     }
 }
 
-#[when("a directory is created")]
-fn a_directory_is_created(world: &mut IncrementalWorld) {
-    if let IncrementalWorld::Successful { ref mut store, .. } = world {
-        let root = store
-            .show(".", TraversalOrder::LevelOrder)
-            .expect("could not find root directory")
-            .next()
-            .expect("could not find root directory")
-            .directory()
-            .expect("Root is not a directory");
-
-        root.directory_insertion("Warp Nacelles")
-            .insert(store)
-            .expect("Could not create Wrap Nacelles directory");
+#[then("a store error is emitted that the batch was committed change by change")]
+fn a_store_error_is_emitted_that_the_batch_was_not_atomic(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        assert!(
+            store
+                .errors()
+                .any(|err| matches!(err, StoreError::BatchNotClean(_))),
+            "Expected store error not emitted: {:?}",
+            store.errors().collect::<Vec<_>>(),
+        );
     } else {
         panic!("World state is not Successful!");
     }
 }
 
-#[when("a password is created in the new directory")]
-fn a_password_is_created_in_the_new_directory(world: &mut IncrementalWorld) {
-    // This is needed to move out of AssertUnwindSafe
-    let prev = std::mem::replace(world, IncrementalWorld::Initial);
-
-    if let IncrementalWorld::Successful {
-        mut store,
-        home,
-        envs,
-    } = prev
-    {
-        let warp_nacelles = store
-            .show("./Warp Nacelles", TraversalOrder::LevelOrder)
-            .expect("could not get root directory of password store")
-            .next()
-            .expect("could not get root directory of password store")
-            .directory()
-            .expect("Root directory is not a directory");
-
-        let password = warp_nacelles
-            .password_insertion("Starfleet")
-            .passphrase("two-nacelles-ftw")
-            .insert(&mut store)
-            .expect("Password insertion failed");
-
-        *world = IncrementalWorld::NewPasswordAndDirectory {
-            store,
-            home,
-            envs,
-            password,
+#[then(regex = r#"^the restored entry is a (password|directory)$"#)]
+fn the_restored_entry_is_a(world: &mut IncrementalWorld, kind: String) {
+    if let IncrementalWorld::RestoredEntry { entry, .. } = world {
+        let expected = match kind.as_str() {
+            "password" => EntryKind::Password,
+            "directory" => EntryKind::Directory,
+            _ => unreachable!(),
         };
+        assert_eq!(entry.kind(), expected);
     } else {
-        panic!("World state is not Successful!");
+        panic!("World state is not RestoredEntry!");
     }
 }
 
-#[when("a directory is renamed")]
-fn a_directory_is_renamed(world: &mut IncrementalWorld) {
-    // This is needed to move out of AssertUnwindSafe
-    let prev = std::mem::replace(world, IncrementalWorld::Initial);
-
-    if let IncrementalWorld::Successful {
-        mut store,
-        home,
-        envs,
-    } = prev
-    {
-        let mut holo_deck = store
-            .show("Entertainment/Holo Deck", TraversalOrder::LevelOrder)
-            .expect("could not find Entertainment/Holo Deck directory")
+#[then(regex = r#"^the restored password "(.*)" has the passphrase "(.*)" and (\d+) lines$"#)]
+fn the_restored_password_has(
+    world: &mut IncrementalWorld,
+    name: String,
+    passphrase: String,
+    lines: usize,
+) {
+    if let IncrementalWorld::RestoredEntry { store, .. } = world {
+        let password = store
+            .show(&name, TraversalOrder::PreOrder)
+            .expect("Restored password is missing in the store")
             .next()
-            .expect("could not find Entertainment/Holo Deck directory")
-            .directory()
-            .expect("Holo Deck is not a directory")
-            .make_mut(&mut store);
-        holo_deck
-            .rename("Novels")
-            .expect("Could not rename directory");
-        let directory = holo_deck.make_immut();
-
-        *world = IncrementalWorld::RenamedDirectory {
-            store,
-            home,
-            envs,
-            directory,
-        };
+            .expect("Restored password is missing in the store")
+            .password()
+            .expect("Restored entry is not a password")
+            .decrypt()
+            .expect("Could not decrypt restored password");
+        assert_eq!(password.passphrase(), Some(passphrase.as_str()));
+        assert_eq!(password.lines().count(), lines);
     } else {
-        panic!("World state is not Successful!");
+        panic!("World state is not RestoredEntry!");
     }
 }
 
-#[when("a directory is removed")]
-fn a_directory_is_removed(world: &mut IncrementalWorld) {
-    // This is needed to move out of AssertUnwindSafe
-    let prev = std::mem::replace(world, IncrementalWorld::Initial);
+#[then(regex = r#"^the restored password "(.*)" is encrypted for (\d+) recipients$"#)]
+fn the_restored_password_is_encrypted_for(
+    world: &mut IncrementalWorld,
+    name: String,
+    recipients: usize,
+) {
+    if let IncrementalWorld::RestoredEntry { store, .. } = world {
+        let encrypted = std::fs::read(store.location().join(format!("{}.gpg", name)))
+            .expect("Could not read restored password");
+        let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)
+            .expect("Could not create OpenPGP context");
+        let mut content = Vec::new();
+        let result = ctx
+            .decrypt(&encrypted, &mut content)
+            .expect("Could not decrypt restored password");
+        assert_eq!(result.recipients().count(), recipients);
+    } else {
+        panic!("World state is not RestoredEntry!");
+    }
+}
 
-    if let IncrementalWorld::Successful {
-        mut store,
-        home,
-        envs,
-    } = prev
-    {
-        let entertainment = store
-            .show("Entertainment", TraversalOrder::LevelOrder)
-            .expect("could not find Entertainment directory")
-            .next()
-            .expect("could not find Entertainment directory")
-            .directory()
-            .expect("Entertainment is not a directory")
-            .make_mut(&mut store);
-        let path = entertainment.path().to_owned();
-        entertainment
-            .remove(Traversal::Recursive)
-            .expect("Could not remove directory");
+#[then(regex = r#"^the restore of "(.*)" is committed$"#)]
+fn the_restore_is_committed(world: &mut IncrementalWorld, name: String) {
+    if let IncrementalWorld::RestoredEntry { envs, .. } = world {
+        let output = Command::new("pass")
+            .args(&["git", "status", "--porcelain"])
+            .envs(envs.clone())
+            .stdout(Stdio::piped())
+            .output()
+            .expect("Could not check git state");
+        let stdout = String::from_utf8(output.stdout).expect("Could not read stdout as UTF-8");
+        assert_eq!(stdout, "", "Repository not clean");
 
-        *world = IncrementalWorld::RemovedDirectory {
-            store,
-            home,
-            envs,
-            path,
-        };
+        let output = Command::new("pass")
+            .args(&["git", "log", "-1", "--pretty=format:%s"])
+            .envs(envs.clone())
+            .stdout(Stdio::piped())
+            .output()
+            .expect("Could not check git commit");
+        let stdout = String::from_utf8(output.stdout).expect("Could not read stdout as UTF-8");
+        assert!(stdout.starts_with(&format!("Restore '{}' from commit ", name)));
     } else {
-        panic!("World state is not Successful!");
+        panic!("World state is not RestoredEntry!");
     }
 }
 
@@ -1209,16 +2736,18 @@ fn the_commit_is_pushed_to_the_remote(world: &mut IncrementalWorld) {
         ..
     } = prev
     {
+        let mut progress = Vec::new();
         let result = store
             .git()
             .expect("Store not using git")
-            .push(GitRemote::UpstreamForBranch);
+            .push_with_progress(GitRemote::UpstreamForBranch, |event| progress.push(event));
 
         *world = IncrementalWorld::Pushed {
             store,
             home,
             envs,
             result,
+            progress,
         };
     } else {
         panic!("World state is not Successful!");
@@ -1520,3 +3049,219 @@ fn the_passwords_and_directories_are_iterated_in_post_order_form(world: &mut Inc
         ],
     );
 }
+
+#[cfg(feature = "otp")]
+fn open_parsed_password(store: &Store, path: &str) -> pass::parsed::DecryptedPassword {
+    store
+        .show(path, TraversalOrder::PreOrder)
+        .unwrap()
+        .next()
+        .expect("OTP password not found in password store!")
+        .password()
+        .expect("OTP password is not a password but a directory!")
+        .decrypt()
+        .expect("Decrypting OTP password failed!")
+        .parsed()
+        .expect("Parsing OTP password failed!")
+}
+
+#[cfg(feature = "otp")]
+#[then(regex = r"^the TOTP code (\d+) seconds after the epoch is (\d+) and rotates in (\d+) seconds?$")]
+fn the_totp_code_is(world: &mut IncrementalWorld, seconds: u64, code: String, remaining: u64) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        let password = open_parsed_password(store, "./Starfleet/Engineering.gpg");
+        let otp = password.otp().expect("Password does not contain an OTP URI");
+        assert_eq!(otp.kind(), pass::parsed::OtpKind::Totp { period: 30 });
+        assert_eq!(otp.issuer(), Some("Starfleet"));
+
+        let time = std::time::UNIX_EPOCH + Duration::from_secs(seconds);
+        let otp_code = password
+            .totp_code_at(time)
+            .expect("Generating TOTP code failed");
+        assert_eq!(otp_code.code(), code);
+        assert_eq!(otp_code.seconds_remaining(), Some(remaining));
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[cfg(feature = "otp")]
+#[then(regex = r"^the next HOTP code is (\d+) and the counter is saved as (\d+)$")]
+fn the_next_hotp_code_is(world: &mut IncrementalWorld, code: String, counter: u64) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        let mut password = open_parsed_password(store, "./Starfleet/Sickbay.gpg");
+        let otp_code = password
+            .hotp_code(store)
+            .expect("Generating HOTP code failed");
+        assert_eq!(otp_code.code(), code);
+        assert_eq!(otp_code.seconds_remaining(), None);
+
+        let password = open_parsed_password(store, "./Starfleet/Sickbay.gpg");
+        let otp = password.otp().expect("Password does not contain an OTP URI");
+        assert_eq!(otp.kind(), pass::parsed::OtpKind::Hotp { counter });
+        assert!(password
+            .passphrase()
+            .unwrap()
+            .contains(&format!("&counter={}", counter)));
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[cfg(feature = "otp")]
+const OTP_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+#[cfg(feature = "otp")]
+#[when("an OTP-only password is inserted from an otpauth URI")]
+fn an_otp_only_password_is_inserted(world: &mut IncrementalWorld) {
+    // This is needed to move out of AssertUnwindSafe
+    let prev = std::mem::replace(world, IncrementalWorld::Initial);
+
+    if let IncrementalWorld::Successful {
+        mut store,
+        home,
+        envs,
+    } = prev
+    {
+        let mut root = store
+            .show("./", TraversalOrder::LevelOrder)
+            .expect("could not get root directory of password store")
+            .next()
+            .expect("could not get root directory of password store")
+            .directory()
+            .expect("Root directory is not a directory");
+
+        let uri = format!(
+            "otpauth://totp/Starfleet:riker?secret={}&issuer=Starfleet&digits=8",
+            OTP_SECRET,
+        );
+        let password = root
+            .parsed_password_insertion("Bridge")
+            .otp_uri(&uri)
+            .expect("Valid otpauth URI was rejected")
+            .insert(&mut store)
+            .expect("Password insertion failed");
+
+        *world = IncrementalWorld::NewPassword {
+            store,
+            home,
+            envs,
+            password,
+        };
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[cfg(feature = "otp")]
+#[then(regex = r"^the new OTP password generates the TOTP code (\d+) (\d+) seconds after the epoch$")]
+fn the_new_otp_password_generates(world: &mut IncrementalWorld, code: String, seconds: u64) {
+    if let IncrementalWorld::NewPassword { password, .. } = world {
+        let password = password
+            .decrypt()
+            .expect("Decrypting new OTP password failed")
+            .parsed()
+            .expect("Parsing new OTP password failed");
+        assert_eq!(password.lines().count(), 0, "OTP-only password contains more lines");
+
+        let time = std::time::UNIX_EPOCH + Duration::from_secs(seconds);
+        let otp_code = password
+            .totp_code_at(time)
+            .expect("Generating TOTP code failed");
+        assert_eq!(otp_code.code(), code);
+    } else {
+        panic!("World state is not NewPassword!");
+    }
+}
+
+#[cfg(feature = "otp")]
+#[then("inserting an OTP secret with an invalid base32 secret fails")]
+fn inserting_an_invalid_otp_secret_fails(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        let mut root = store
+            .show("./", TraversalOrder::LevelOrder)
+            .expect("could not get root directory of password store")
+            .next()
+            .expect("could not get root directory of password store")
+            .directory()
+            .expect("Root directory is not a directory");
+
+        let mut inserter = root.parsed_password_insertion("Bridge");
+        let result = inserter.otp_uri("otpauth://totp/Starfleet:riker?secret=NOT*BASE32");
+        assert!(matches!(result, Err(StoreError::InvalidOtpUri(_))));
+
+        let result = pass::parsed::OtpBuilder::totp(OTP_SECRET, "riker")
+            .digits(12)
+            .build();
+        assert!(matches!(result, Err(StoreError::InvalidOtpUri(_))));
+        let result = pass::parsed::OtpBuilder::totp(OTP_SECRET, "riker")
+            .period(0)
+            .build();
+        assert!(matches!(result, Err(StoreError::InvalidOtpUri(_))));
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[cfg(feature = "otp")]
+#[when("an OTP secret is added to an existing password")]
+fn an_otp_secret_is_added_to_an_existing_password(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        let otp = pass::parsed::OtpBuilder::totp(OTP_SECRET.to_lowercase(), "laforge")
+            .issuer("Starfleet")
+            .digits(8)
+            .build()
+            .expect("Building OTP secret failed");
+        let mut password = open_parsed_password(store, "./Manufacturers/Yoyodyne.gpg");
+        password
+            .set_otp(store, &otp)
+            .expect("Adding OTP secret to password failed");
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[cfg(feature = "otp")]
+#[then(regex = r"^the password keeps its passphrase and generates the TOTP code (\d+) (\d+) seconds after the epoch$")]
+fn the_password_keeps_its_passphrase_and_generates(
+    world: &mut IncrementalWorld,
+    code: String,
+    seconds: u64,
+) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        let password = open_parsed_password(store, "./Manufacturers/Yoyodyne.gpg");
+        assert_eq!(password.passphrase(), Some("all1the%fancy@panels+are;for<me"));
+        assert_eq!(password.entry("user").map(|(_, value)| value), Some("laforge"));
+
+        let time = std::time::UNIX_EPOCH + Duration::from_secs(seconds);
+        let otp_code = password
+            .totp_code_at(time)
+            .expect("Generating TOTP code failed");
+        assert_eq!(otp_code.code(), code);
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[cfg(all(feature = "otp-qr", feature = "qr-codes"))]
+#[then("the OTP secret of the new password can be read back from a QR image")]
+fn the_otp_secret_can_be_read_from_a_qr_image(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::NewPassword { password, .. } = world {
+        let password = password
+            .decrypt()
+            .expect("Decrypting new OTP password failed")
+            .parsed()
+            .expect("Parsing new OTP password failed");
+        let otp = password.otp().expect("Password does not contain an OTP URI");
+        let image = password
+            .passphrase_qr_code()
+            .expect("Could not create QR code for OTP URI")
+            .to_png()
+            .expect("Could not render QR code as PNG");
+
+        let scanned = pass::parsed::Otp::from_qr_image(&image).expect("Reading QR image failed");
+        assert_eq!(scanned.to_uri(), otp.to_uri());
+    } else {
+        panic!("World state is not NewPassword!");
+    }
+}
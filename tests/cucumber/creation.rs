@@ -4,7 +4,10 @@ use std::panic::AssertUnwindSafe;
 
 use cucumber::{given, then, when};
 use gpgme::PassphraseRequest;
-use pass::{Location, PassphraseProvider, SigningKey, Sorting, StoreBuilder, Umask};
+use pass::{
+    CredentialsRequest, GitProgress, Location, PassphraseProvider, SigningKey, Sorting,
+    StoreBuilder, StoreError, Umask,
+};
 
 use crate::world::IncrementalWorld;
 
@@ -100,6 +103,38 @@ fn the_passwords_in_the_store_are_sorted(world: &mut IncrementalWorld) {
     }
 }
 
+#[given("the password store keeps a search index")]
+fn the_password_store_keeps_a_search_index(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Prepared {
+        builder: AssertUnwindSafe(ref mut builder),
+        home,
+        ..
+    } = world
+    {
+        builder.search_index(home.path().join("search-index.gpg"));
+    } else {
+        panic!("World state is not Prepared!");
+    }
+}
+
+#[given("credentials are requested from the application")]
+fn credentials_are_requested_from_the_application(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Prepared {
+        builder: AssertUnwindSafe(ref mut builder),
+        ..
+    } = world
+    {
+        builder.credentials_provider(|request: &CredentialsRequest| {
+            Err(git2::Error::from_str(&format!(
+                "no credentials for {}",
+                request.url()
+            )))
+        });
+    } else {
+        panic!("World state is not Prepared!");
+    }
+}
+
 #[given("no signing key is specified")]
 fn no_signing_key_is_specified(world: &mut IncrementalWorld) {
     if let IncrementalWorld::Initial = world {
@@ -255,7 +290,7 @@ fn pass_extensions_are_enabled_in_the_environment(world: &mut IncrementalWorld)
     }
 }
 
-#[given("pass extensions directory is set in the environment")]
+#[given("the pass extensions directory is set over the environment")]
 fn pass_extensions_directory_is_set_in_the_environment(world: &mut IncrementalWorld) {
     if let IncrementalWorld::Initial = world {
         *world = IncrementalWorld::clean_env("env").unwrap();
@@ -289,6 +324,20 @@ fn a_foreign_gpg_id_is_set_via_the_environment(world: &mut IncrementalWorld) {
     }
 }
 
+#[given("a clipboard duration of 1 second is specified in the environment")]
+fn a_clipboard_duration_of_1_second_is_specified_in_the_environment(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Initial = world {
+        *world = IncrementalWorld::clean_env("env").unwrap();
+    }
+
+    if let IncrementalWorld::Prepared { envs, .. } = world {
+        env::set_var("PASSWORD_STORE_CLIP_TIME", "1");
+        envs.insert("PASSWORD_STORE_CLIP_TIME".to_owned(), "1".to_owned());
+    } else {
+        panic!("World state is not Prepared!");
+    }
+}
+
 #[given("the X selection is set to clipboard in the environment")]
 fn the_x_selection_is_set_to_clipboard_in_the_environment(world: &mut IncrementalWorld) {
     if let IncrementalWorld::Initial = world {
@@ -346,7 +395,11 @@ fn a_new_password_store_is_initialized(world: &mut IncrementalWorld, location: S
     let prev = std::mem::replace(world, IncrementalWorld::Initial);
 
     if let IncrementalWorld::Prepared {
-        home, key_id, envs, ..
+        home,
+        key_id,
+        envs,
+        mut builder,
+        ..
     } = prev
     {
         let location = match location.as_str() {
@@ -363,7 +416,7 @@ fn a_new_password_store_is_initialized(world: &mut IncrementalWorld, location: S
             }
         };
         *world = IncrementalWorld::Created {
-            store: AssertUnwindSafe(StoreBuilder::default().location(location).init(&key_id)),
+            store: AssertUnwindSafe(builder.location(location).init(&key_id)),
             home,
             envs,
         };
@@ -377,7 +430,13 @@ fn a_password_store_is_opened(world: &mut IncrementalWorld, location: String) {
     // This is needed to move out of AssertUnwindSafe
     let prev = std::mem::replace(world, IncrementalWorld::Initial);
 
-    if let IncrementalWorld::Prepared { home, envs, .. } = prev {
+    if let IncrementalWorld::Prepared {
+        home,
+        envs,
+        mut builder,
+        ..
+    } = prev
+    {
         let location = match location.as_str() {
             "" => Location::Automatic,
             " at a manually provided location" => {
@@ -393,7 +452,34 @@ fn a_password_store_is_opened(world: &mut IncrementalWorld, location: String) {
         };
 
         *world = IncrementalWorld::Created {
-            store: AssertUnwindSafe(StoreBuilder::default().location(location).open()),
+            store: AssertUnwindSafe(builder.location(location).open()),
+            home,
+            envs,
+        };
+    } else {
+        panic!("World state is not Prepared!");
+    }
+}
+
+#[given("the password store is cloned from the remote")]
+fn the_password_store_is_cloned_from_the_remote(world: &mut IncrementalWorld) {
+    // This is needed to move out of AssertUnwindSafe
+    let prev = std::mem::replace(world, IncrementalWorld::Initial);
+
+    if let IncrementalWorld::Prepared { home, mut envs, .. } = prev {
+        let remote = home.path().join("password-store-remote");
+        let path = home.path().join("cloned-password-store");
+        envs.insert(
+            "PASSWORD_STORE_DIR".to_owned(),
+            format!("{}", path.display()),
+        );
+
+        *world = IncrementalWorld::Created {
+            store: AssertUnwindSafe(
+                StoreBuilder::default()
+                    .location(Location::Manual(path))
+                    .clone_remote(&format!("file://{}", remote.display())),
+            ),
             home,
             envs,
         };
@@ -430,6 +516,49 @@ fn the_repositorys_remote_is_fetched(world: &mut IncrementalWorld) {
         .fetch().expect("failed to fetch remote");
 }
 
+#[then("fetching the remote fails without credentials from the application")]
+fn fetching_the_remote_fails_without_credentials_from_the_application(
+    world: &mut IncrementalWorld,
+) {
+    let store = match world {
+        IncrementalWorld::Successful { store, .. } => store,
+        _ => panic!("World state is invalid!"),
+    };
+
+    let err = store
+        .git().expect("store is not using git")
+        .fetch().expect_err("fetched from remote without credentials");
+    assert!(
+        err.message().contains("no credentials for http://127.0.0.1"),
+        "Application was not asked for credentials: {}",
+        err
+    );
+}
+
+#[then("fetching the remote reports the received objects")]
+fn fetching_the_remote_reports_the_received_objects(world: &mut IncrementalWorld) {
+    let store = match world {
+        IncrementalWorld::Successful { store, .. } => store,
+        _ => panic!("World state is invalid!"),
+    };
+
+    let mut transfers = Vec::new();
+    store
+        .git().expect("store is not using git")
+        .fetch_with_progress(|event| {
+            if let GitProgress::Transfer(transfer) = event {
+                transfers.push(transfer);
+            }
+        })
+        .expect("failed to fetch remote");
+
+    let last = transfers.last().expect("No transfer progress reported");
+    assert!(last.total_objects() > 0, "No objects were fetched");
+    assert_eq!(last.received_objects(), last.total_objects());
+    assert_eq!(last.indexed_objects(), last.total_objects());
+    assert!(last.received_bytes() > 0, "No bytes were transferred");
+}
+
 #[then(regex = "the (.*) of the password store fails")]
 fn the_operation_of_the_password_store_fails(world: &mut IncrementalWorld) {
     // This is needed to move out of AssertUnwindSafe
@@ -459,3 +588,59 @@ fn the_password_store_has_no_errors(world: &mut IncrementalWorld) {
         panic!("World state is not Successful!");
     }
 }
+
+fn assert_store_error(world: &IncrementalWorld, predicate: impl Fn(&StoreError) -> bool) {
+    if let IncrementalWorld::Successful { store, .. } = world {
+        if !store.errors().any(predicate) {
+            panic!(
+                "Expected store error not emitted: {:?}",
+                store.errors().collect::<Vec<_>>(),
+            );
+        }
+    } else {
+        panic!("World state is not Successful!");
+    }
+}
+
+#[then("a store error is emitted that the overriden password store key from the environment is a security risk")]
+fn a_store_error_is_emitted_that_the_password_store_key_is_a_security_risk(world: &mut IncrementalWorld) {
+    assert_store_error(world, |err| matches!(err, StoreError::EnvGpgIdOverride));
+}
+
+#[then("a store error is emitted that the generated password length from the environment is ingored due to security reasons")]
+fn a_store_error_is_emitted_that_the_generated_password_length_is_ignored(world: &mut IncrementalWorld) {
+    assert_store_error(world, |err| matches!(err, StoreError::EnvGeneratedLength));
+}
+
+#[then("a store error is emitted that the character set from the environment is ingored due to security reasons")]
+fn a_store_error_is_emitted_that_the_character_set_is_ignored(world: &mut IncrementalWorld) {
+    assert_store_error(world, |err| {
+        matches!(err, StoreError::EnvCharacterSet("PASSWORD_STORE_CHARACTER_SET"))
+    });
+}
+
+#[then("a store error is emitted that the ignored symbols from the environment is ingored due to security reasons")]
+fn a_store_error_is_emitted_that_the_ignored_symbols_are_ignored(world: &mut IncrementalWorld) {
+    assert_store_error(world, |err| {
+        matches!(err, StoreError::EnvCharacterSet("PASSWORD_STORE_CHARACTER_SET_NO_SYMBOLS"))
+    });
+}
+
+#[then("a store error is emitted that GPG options from the environment are ingored due to security reasons")]
+fn a_store_error_is_emitted_that_gpg_options_are_ignored(world: &mut IncrementalWorld) {
+    assert_store_error(world, |err| matches!(err, StoreError::EnvGpgOptions));
+}
+
+#[then("a store error is emitted that extensions are not supported")]
+fn a_store_error_is_emitted_that_extensions_are_not_supported(world: &mut IncrementalWorld) {
+    assert_store_error(world, |err| {
+        matches!(err, StoreError::EnvExtensions("PASSWORD_STORE_ENABLE_EXTENSIONS"))
+    });
+}
+
+#[then("a store error is emitted that extension directories are ignored as extensions are not supported")]
+fn a_store_error_is_emitted_that_extension_directories_are_ignored(world: &mut IncrementalWorld) {
+    assert_store_error(world, |err| {
+        matches!(err, StoreError::EnvExtensions("PASSWORD_STORE_EXTENSIONS_DIR"))
+    });
+}
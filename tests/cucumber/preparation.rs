@@ -1,6 +1,8 @@
 use std::collections::HashMap;
-use std::io::Write;
+use std::io::{Read, Write};
+use std::net::TcpListener;
 use std::process::{Command, Stdio};
+use std::thread;
 use std::fs::{File, OpenOptions};
 use std::path::{PathBuf, Component};
 
@@ -159,6 +161,100 @@ fn passwords_are_stored_in_the_password_store(world: &mut IncrementalWorld) {
     }
 }
 
+#[given(regex = r#"^commits are signed with "(.*)"$"#)]
+fn commits_are_signed_with(world: &mut IncrementalWorld, key: String) {
+    if let IncrementalWorld::Prepared { envs, home, .. } = world {
+        let store_path = envs
+            .get("PASSWORD_STORE_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.path().join(".password-store"));
+        for (name, value) in &[("commit.gpgsign", "true"), ("user.signingkey", key.as_str())] {
+            let status = Command::new("git")
+                .args(&["config", name, value])
+                .envs(envs.clone())
+                .current_dir(&store_path)
+                .status()
+                .expect("Failed to configure commit signing");
+            assert!(status.success(), "Failed to set {} in git config", name);
+        }
+    } else {
+        panic!("World state is not Prepared!");
+    }
+}
+
+#[given(regex = r#"^the password "(.*)" is corrupted$"#)]
+fn the_password_is_corrupted(world: &mut IncrementalWorld, name: String) {
+    if let IncrementalWorld::Prepared { envs, home, .. } = world {
+        let store_path = envs
+            .get("PASSWORD_STORE_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.path().join(".password-store"));
+        let password = store_path.join(format!("{}.gpg", name));
+        std::fs::write(&password, b"not an OpenPGP message")
+            .expect("Could not corrupt password");
+    } else {
+        panic!("World state is not Prepared!");
+    }
+}
+
+#[given(regex = r#"^the password "(.*)" cannot be replaced$"#)]
+fn the_password_cannot_be_replaced(world: &mut IncrementalWorld, name: String) {
+    if let IncrementalWorld::Prepared { envs, home, .. } = world {
+        let store_path = envs
+            .get("PASSWORD_STORE_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.path().join(".password-store"));
+        // unlike permissions, the immutable attribute also stops root from renaming over it
+        let status = Command::new("chattr")
+            .arg("+i")
+            .arg(store_path.join(format!("{}.gpg", name)))
+            .status()
+            .expect("Could not run chattr");
+        assert!(status.success(), "Could not make the password immutable");
+    } else {
+        panic!("World state is not Prepared!");
+    }
+}
+
+#[given(regex = r#"^the directory "(.*)" is also encrypted for "(.*)"$"#)]
+fn the_directory_is_also_encrypted_for(world: &mut IncrementalWorld, dir: String, gpg_id: String) {
+    if let IncrementalWorld::Prepared { envs, key_id, .. } = world {
+        let status = Command::new("pass")
+            .args(&["init", "--path", &dir, key_id.as_str(), &gpg_id])
+            .env_clear()
+            .envs(envs.iter())
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "Failed to set the GPG IDs of {}!", dir);
+    } else {
+        panic!("World state is not Prepared!");
+    }
+}
+
+#[given("OTP secrets are stored in the password store")]
+fn otp_secrets_are_stored_in_the_password_store(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Prepared { envs, .. } = world {
+        // secret from the RFC 4226 and RFC 6238 test vectors
+        insert_password(
+            envs,
+            "Starfleet/Engineering",
+            "warp-core-breach\n\
+             otpauth://totp/Starfleet:laforge?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\
+             &issuer=Starfleet&digits=8\n",
+        );
+        // pass-otp stores the URI on the first line when inserting a new OTP password
+        insert_password(
+            envs,
+            "Starfleet/Sickbay",
+            "otpauth://hotp/Starfleet:crusher?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\
+             &issuer=Starfleet&counter=1\n",
+        );
+    } else {
+        panic!("World state is not Prepared!");
+    }
+}
+
 #[given("the repository has a remote")]
 fn the_repository_has_a_remote(world: &mut IncrementalWorld) {
     if let IncrementalWorld::Prepared { envs, home, .. } = world {
@@ -216,6 +312,56 @@ fn the_repository_has_a_remote(world: &mut IncrementalWorld) {
     }
 }
 
+#[given("the repository has a remote which requires authentication")]
+fn the_repository_has_a_remote_which_requires_authentication(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Prepared { envs, home, .. } = world {
+        let password_store_dir = if let Some(path) = envs.get("PASSWORD_STORE_DIR") {
+            path.into()
+        } else {
+            home.path().join(".password-store")
+        };
+
+        // a fake HTTP remote which rejects every request until credentials are sent
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind fake remote");
+        let address = listener.local_addr().expect("Fake remote has no address");
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                    match stream.read(&mut buffer) {
+                        Ok(0) | Err(_) => break,
+                        Ok(read) => request.extend_from_slice(&buffer[..read]),
+                    }
+                }
+                let _ = stream.write_all(
+                    b"HTTP/1.1 401 Unauthorized\r\n\
+                      WWW-Authenticate: Basic realm=\"password-store\"\r\n\
+                      Content-Length: 0\r\n\
+                      Connection: close\r\n\r\n",
+                );
+            }
+        });
+
+        let status = Command::new("git")
+            .arg("remote")
+            .arg("add")
+            .arg("origin")
+            .arg(format!("http://{}/password-store.git", address))
+            .envs(envs.clone())
+            .current_dir(&password_store_dir)
+            .status()
+            .expect("failed to set origin in password store");
+        assert!(status.success(), "Failed to add remote to repository");
+    } else {
+        panic!("World state is not Prepared!");
+    }
+}
+
 #[given("the repository's remote contains new commits")]
 fn the_repositorys_remote_contains_new_commits(world: &mut IncrementalWorld) {
     if let IncrementalWorld::Prepared { envs, home, .. } = world {
@@ -683,6 +829,43 @@ fn the_repositorys_remote_contains_new_commits_changing_the_gpg_id(world: &mut I
     }
 }
 
+#[given("the git user is configured")]
+fn the_git_user_is_configured(world: &mut IncrementalWorld) {
+    if let IncrementalWorld::Prepared { envs, .. } = world {
+        let status = Command::new("git")
+            .args(&["config", "--global", "user.name", "Test User"])
+            .envs(envs.clone())
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "Failed to set username in git config");
+
+        let status = Command::new("git")
+            .args(&["config", "--global", "user.email", "test@key.email"])
+            .envs(envs.clone())
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "Failed to set email in git config");
+
+        let status = Command::new("git")
+            .args(&["config", "--global", "init.defaultBranch", "main"])
+            .envs(envs.clone())
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "Failed to set default branch name in git config");
+
+        unsafe {
+            // update cached global git config in libgit2
+            libgit2_sys::git_libgit2_shutdown();
+            libgit2_sys::git_libgit2_init();
+        }
+    } else {
+        panic!("World state is not Prepared!");
+    }
+}
+
 #[given("the git username is not set")]
 fn the_git_username_is_not_set(world: &mut IncrementalWorld) {
     if let IncrementalWorld::Prepared { envs, .. } = world {
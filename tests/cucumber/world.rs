@@ -2,10 +2,14 @@ use std::collections::HashMap;
 use std::env;
 use std::panic::AssertUnwindSafe;
 use std::path::PathBuf;
+use std::time::Instant;
 
 use anyhow::Context as AnyhowContext;
 use cucumber::World;
-use pass::{ConflictResolver, DecryptedPassword, Directory, Password, Store, StoreBuilder, StoreError};
+use pass::{
+    ClipboardGuard, ConflictResolver, DecryptedPassword, Directory, Entry, FuzzyMatch, GitProgress,
+    GrepResults, MemoryClipboard, Password, Store, StoreBuilder, StoreError,
+};
 use tempfile::TempDir;
 
 use ouroboros::self_referencing;
@@ -46,12 +50,28 @@ pub enum IncrementalWorld {
     DecryptedPassword {
         password: DecryptedPassword,
     },
+    ClipboardPassword {
+        password: DecryptedPassword,
+        clipboard: MemoryClipboard,
+        guard: Option<ClipboardGuard>,
+        copied: Instant,
+    },
     Search {
         found_entries: Vec<PathBuf>,
     },
     Grep {
         found_passwords: Vec<DecryptedPassword>,
     },
+    FuzzySearch {
+        matches: Vec<FuzzyMatch>,
+    },
+    ParallelGrep {
+        results: GrepResults,
+    },
+    #[cfg(feature = "parsed-passwords")]
+    FieldSearch {
+        results: pass::parsed::FieldResults,
+    },
     NewPassword {
         home: TempDir,
         store: AssertUnwindSafe<Store>,
@@ -76,6 +96,18 @@ pub enum IncrementalWorld {
         password: Password,
         envs: HashMap<String, String>,
     },
+    MovedPassword {
+        home: TempDir,
+        store: AssertUnwindSafe<Store>,
+        password: Password,
+        envs: HashMap<String, String>,
+    },
+    CopiedPassword {
+        home: TempDir,
+        store: AssertUnwindSafe<Store>,
+        password: Password,
+        envs: HashMap<String, String>,
+    },
     NewPasswordAndDirectory {
         home: TempDir,
         store: AssertUnwindSafe<Store>,
@@ -94,11 +126,18 @@ pub enum IncrementalWorld {
         path: PathBuf,
         envs: HashMap<String, String>,
     },
+    RestoredEntry {
+        home: TempDir,
+        store: AssertUnwindSafe<Store>,
+        entry: Entry,
+        envs: HashMap<String, String>,
+    },
     Pushed {
         home: TempDir,
         store: AssertUnwindSafe<Store>,
         envs: HashMap<String, String>,
         result: Result<(), git2::Error>,
+        progress: Vec<GitProgress>,
     },
     Pulled {
         home: TempDir,